@@ -0,0 +1,46 @@
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use interpreter::{
+    compiler::compiler::Compiler, evaluator::evaluator::eval, lexer::lexer::Lexer,
+    object::environment::Environment, parser::parser::Parser, vm::vm::Vm,
+};
+
+const SAMPLE: &str = "let fibonacci = fn(n) {
+    if (n < 2) {
+        return n;
+    } else {
+        fibonacci(n - 1) + fibonacci(n - 2);
+    }
+};
+fibonacci(25);
+";
+
+fn best_of(runs: usize, mut f: impl FnMut() -> String) -> (String, Duration) {
+    let mut result = String::new();
+    let mut best = Duration::MAX;
+    for _ in 0..runs {
+        let start = Instant::now();
+        result = black_box(f());
+        best = best.min(start.elapsed());
+    }
+    (result, best)
+}
+
+fn main() {
+    let mut parser = Parser::new(Lexer::new(SAMPLE));
+    let program = parser.parse_program();
+
+    let (result, evaluator) = best_of(5, || eval(black_box(&program), &Environment::new()).to_string());
+    println!("evaluator: fibonacci(25) = {} in {:.2?}", result, evaluator);
+
+    let (result, vm) = best_of(5, || {
+        let mut compiler = Compiler::new();
+        compiler.compile(black_box(&program)).unwrap();
+        Vm::new(compiler.bytecode()).run().to_string()
+    });
+    println!("vm:        fibonacci(25) = {} in {:.2?}", result, vm);
+    println!("speedup:   {:.1}x", evaluator.as_secs_f64() / vm.as_secs_f64());
+}
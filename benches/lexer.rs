@@ -0,0 +1,160 @@
+use std::{
+    borrow::Cow,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use interpreter::{
+    lexer::lexer::Lexer,
+    token::token::{Span, Token, Tokens},
+};
+
+const SAMPLE: &str = "let fibonacci = fn(n) {
+    if (n < 2) {
+        return n;
+    } else {
+        fibonacci(n - 1) + fibonacci(n - 2);
+    }
+};
+let result = fibonacci(25) * 10 / 3 - 7;
+let check = !(result == 12345) != false;
+";
+
+/// The lexer as it was before it worked on bytes: the source is copied into
+/// a `Vec<char>` with a table of byte offsets, and every token owns its
+/// literal. It only knows the tokens `SAMPLE` uses.
+struct CharLexer {
+    input: Vec<char>,
+    offsets: Vec<usize>,
+    index: usize,
+    line: usize,
+    line_start: usize,
+    done: bool,
+}
+
+impl CharLexer {
+    fn new(source: &str) -> Self {
+        let mut offsets: Vec<usize> = source.char_indices().map(|(offset, _)| offset).collect();
+        offsets.push(source.len());
+        Self {
+            input: source.chars().collect(),
+            offsets,
+            index: 0,
+            line: 1,
+            line_start: 0,
+            done: false,
+        }
+    }
+
+    fn current_char(&self) -> Option<char> {
+        self.input.get(self.index).copied()
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input.get(self.index + 1).copied()
+    }
+
+    fn read_while(&mut self, accept: impl Fn(char) -> bool) -> String {
+        let start = self.index;
+        while self.current_char().is_some_and(&accept) {
+            self.index += 1;
+        }
+        self.index -= 1;
+        self.input[start..=self.index].iter().collect()
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.offsets[start], self.offsets[end], self.line, start - self.line_start + 1)
+    }
+}
+
+impl Iterator for CharLexer {
+    type Item = Token<'static>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        while let Some(c) = self.current_char().filter(|c| c.is_whitespace()) {
+            if c == '\n' {
+                self.line += 1;
+                self.line_start = self.index + 1;
+            }
+            self.index += 1;
+        }
+        let start = self.index;
+        let Some(current) = self.current_char() else {
+            self.done = true;
+            return Some(Token { token_type: Tokens::EOF, literal: Cow::Borrowed(""), span: self.span(start, start) });
+        };
+        let (token_type, literal) = match current {
+            '=' | '!' if self.peek_char() == Some('=') => {
+                self.index += 1;
+                let token_type = if current == '=' { Tokens::EQ } else { Tokens::NOTEQ };
+                (token_type, format!("{}=", current))
+            }
+            '=' => (Tokens::ASSIGN, String::from('=')),
+            '!' => (Tokens::BANG, String::from('!')),
+            '+' => (Tokens::PLUS, String::from('+')),
+            '-' => (Tokens::MINUS, String::from('-')),
+            '*' => (Tokens::ASTERISK, String::from('*')),
+            '/' => (Tokens::SLASH, String::from('/')),
+            '<' => (Tokens::LT, String::from('<')),
+            '>' => (Tokens::GT, String::from('>')),
+            '(' => (Tokens::LPAREN, String::from('(')),
+            ')' => (Tokens::RPAREN, String::from(')')),
+            '{' => (Tokens::LBRACE, String::from('{')),
+            '}' => (Tokens::RBRACE, String::from('}')),
+            ',' => (Tokens::COMMA, String::from(',')),
+            ';' => (Tokens::SEMICOLON, String::from(';')),
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let literal = self.read_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let token_type = match literal.as_str() {
+                    "let" => Tokens::LET,
+                    "fn" => Tokens::FUNCTION,
+                    "true" => Tokens::TRUE,
+                    "false" => Tokens::FALSE,
+                    "if" => Tokens::IF,
+                    "else" => Tokens::ELSE,
+                    "return" => Tokens::RETURN,
+                    _ => Tokens::IDENT,
+                };
+                (token_type, literal)
+            }
+            '0'..='9' => (Tokens::INT, self.read_while(|c| c.is_ascii_digit())),
+            c => (Tokens::ILLEGAL, String::from(c)),
+        };
+        self.index += 1;
+        Some(Token { token_type, literal: Cow::Owned(literal), span: self.span(start, self.index) })
+    }
+}
+
+/// Runs `lex` ten times and returns its token count with the best time.
+fn best_of_ten(lex: impl Fn() -> usize) -> (usize, Duration) {
+    let mut tokens = 0;
+    let mut best = Duration::MAX;
+    for _ in 0..10 {
+        let start = Instant::now();
+        tokens = black_box(lex());
+        best = best.min(start.elapsed());
+    }
+    (tokens, best)
+}
+
+fn main() {
+    let baseline_tokens: Vec<Token> = CharLexer::new(SAMPLE).collect();
+    let tokens: Vec<Token> = Lexer::new(SAMPLE).into_iter().collect();
+    assert_eq!(baseline_tokens, tokens, "both lexers should produce the same tokens");
+
+    let source = SAMPLE.repeat(20_000);
+    let megabytes = source.len() as f64 / (1024.0 * 1024.0);
+
+    let (baseline_tokens, baseline) = best_of_ten(|| CharLexer::new(black_box(&source)).count());
+    let (tokens, best) = best_of_ten(|| Lexer::new(black_box(&source)).into_iter().count());
+    assert_eq!(tokens, baseline_tokens);
+
+    println!("lexed {:.1} MiB into {} tokens", megabytes, tokens);
+    println!("Vec<char> lexer: {:.2?} ({:.1} MiB/s)", baseline, megabytes / baseline.as_secs_f64());
+    println!("byte lexer:      {:.2?} ({:.1} MiB/s)", best, megabytes / best.as_secs_f64());
+    println!("speedup:         {:.1}x", baseline.as_secs_f64() / best.as_secs_f64());
+}
@@ -1,4 +1,6 @@
-use crate::token::token::Token;
+use std::fmt;
+
+use crate::token::token::{Span, Token};
 
 pub type Program = Statements;
 
@@ -6,19 +8,131 @@ pub type Statements = Vec<Statement>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Identifier {
-    pub token: Token,
+    pub token: Token<'static>,
     pub value: String,
 }
 
 
-#[derive(Debug, PartialEq, Eq)]
+/// Calls, indexing and collection literals keep the span of their closing
+/// delimiter as their last field.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Identifier(Identifier),
+    IntegerLiteral(Token<'static>, i64),
+    FloatLiteral(Token<'static>, f64),
+    StringLiteral(Token<'static>, String),
+    Boolean(Token<'static>, bool),
+    PrefixExpression(Token<'static>, Box<Expression>),
+    InfixExpression(Box<Expression>, Token<'static>, Box<Expression>),
+    CallExpression(Box<Expression>, Vec<Expression>, Span),
+    ArrayLiteral(Token<'static>, Vec<Expression>, Span),
+    IndexExpression(Box<Expression>, Box<Expression>, Span),
+    HashLiteral(Token<'static>, Vec<(Expression, Expression)>, Span),
+    FunctionLiteral(Token<'static>, Vec<Identifier>, BlockStatement),
+    IfExpression(Token<'static>, Box<Expression>, BlockStatement, Option<BlockStatement>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStatement {
+    pub token: Token<'static>,
+    pub statements: Statements,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     LetStatement(Identifier, Expression),
-    ReturnStatement(Token, Expression),
-    ExpressionStatement(Token, Expression),
+    ReturnStatement(Token<'static>, Expression),
+    ExpressionStatement(Expression),
+}
+
+impl Expression {
+    /// The source range the expression was parsed from. Calls, indexing and
+    /// collection literals end at their closing delimiter; blocks keep no
+    /// closing brace, so `if`/`fn` end at the opening brace of their last
+    /// block.
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Identifier(identifier) => identifier.token.span,
+            Expression::IntegerLiteral(token, _)
+            | Expression::FloatLiteral(token, _)
+            | Expression::StringLiteral(token, _)
+            | Expression::Boolean(token, _) => token.span,
+            Expression::PrefixExpression(operator, right) => operator.span.to(right.span()),
+            Expression::InfixExpression(left, _, right) => left.span().to(right.span()),
+            Expression::CallExpression(function, _, close) | Expression::IndexExpression(function, _, close) => {
+                function.span().to(*close)
+            }
+            Expression::ArrayLiteral(token, _, close) | Expression::HashLiteral(token, _, close) => token.span.to(*close),
+            Expression::FunctionLiteral(token, _, body) => token.span.to(body.token.span),
+            Expression::IfExpression(token, _, consequence, alternative) => {
+                let block = alternative.as_ref().unwrap_or(consequence);
+                token.span.to(block.token.span)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Identifier(identifier) => write!(f, "{}", identifier),
+            Expression::IntegerLiteral(token, _) => write!(f, "{}", token.literal),
+            Expression::FloatLiteral(token, _) => write!(f, "{}", token.literal),
+            Expression::StringLiteral(token, _) => write!(f, "{}", token.literal),
+            Expression::Boolean(token, _) => write!(f, "{}", token.literal),
+            Expression::PrefixExpression(operator, right) => {
+                write!(f, "({}{})", operator.literal, right)
+            }
+            Expression::InfixExpression(left, operator, right) => {
+                write!(f, "({} {} {})", left, operator.literal, right)
+            }
+            Expression::CallExpression(function, arguments, _) => {
+                let arguments: Vec<String> = arguments.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", function, arguments.join(", "))
+            }
+            Expression::ArrayLiteral(_, elements, _) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Expression::IndexExpression(left, index, _) => write!(f, "({}[{}])", left, index),
+            Expression::HashLiteral(_, pairs, _) => {
+                let pairs: Vec<String> = pairs.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Expression::FunctionLiteral(token, parameters, body) => {
+                let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+                write!(f, "{}({}) {}", token.literal, parameters.join(", "), body)
+            }
+            Expression::IfExpression(_, condition, consequence, alternative) => {
+                write!(f, "if {} {}", condition, consequence)?;
+                match alternative {
+                    Some(alternative) => write!(f, " else {}", alternative),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let statements: Vec<String> = self.statements.iter().map(|s| s.to_string()).collect();
+        write!(f, "{{ {} }}", statements.join(" "))
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::LetStatement(identifier, value) => write!(f, "let {} = {};", identifier, value),
+            Statement::ReturnStatement(_, value) => write!(f, "return {};", value),
+            Statement::ExpressionStatement(expression) => write!(f, "{}", expression),
+        }
+    }
 }
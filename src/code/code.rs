@@ -0,0 +1,302 @@
+use std::fmt::Write;
+
+use crate::token::token::Span;
+
+pub type Instructions = Vec<u8>;
+
+/// A single VM instruction. Each opcode is one byte, followed by its
+/// operands encoded big-endian with the widths given by its `Definition`.
+#[derive(Debug, PartialEq, Copy, Clone, Eq)]
+#[repr(u8)]
+pub enum Opcode {
+    Constant,
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    True,
+    False,
+    Null,
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessEqual,
+    GreaterEqual,
+    Minus,
+    Bang,
+    Truthy,
+    JumpNotTruthy,
+    Jump,
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    GetFree,
+    GetCell,
+    SetCell,
+    Array,
+    Hash,
+    Hashable,
+    Index,
+    Call,
+    ReturnValue,
+    Return,
+    Closure,
+    CaptureCell,
+    CaptureFree,
+}
+
+/// Every opcode, indexed by its byte value.
+const OPCODES: [Opcode; 38] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::LessThan,
+    Opcode::GreaterThan,
+    Opcode::LessEqual,
+    Opcode::GreaterEqual,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::Truthy,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetFree,
+    Opcode::GetCell,
+    Opcode::SetCell,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Hashable,
+    Opcode::Index,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::Closure,
+    Opcode::CaptureCell,
+    Opcode::CaptureFree,
+];
+
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+impl Opcode {
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::Mod => ("OpMod", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::LessEqual => ("OpLessEqual", &[]),
+            Opcode::GreaterEqual => ("OpGreaterEqual", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::Truthy => ("OpTruthy", &[]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            Opcode::GetCell => ("OpGetCell", &[1]),
+            Opcode::SetCell => ("OpSetCell", &[1]),
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::Hashable => ("OpHashable", &[]),
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            Opcode::Closure => ("OpClosure", &[2, 1]),
+            Opcode::CaptureCell => ("OpCaptureCell", &[1]),
+            Opcode::CaptureFree => ("OpCaptureFree", &[1]),
+        };
+        Definition { name, operand_widths }
+    }
+
+    /// The number of bytes the instruction takes, opcode included.
+    pub fn width(self) -> usize {
+        1 + self.definition().operand_widths.iter().sum::<usize>()
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OPCODES.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+/// Encodes `op` and its operands. Operands wider than their slot are
+/// truncated, so the compiler checks limits before calling this.
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let definition = op.definition();
+    let mut instruction = Vec::with_capacity(op.width());
+    instruction.push(op as u8);
+    for (operand, width) in operands.iter().zip(definition.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+    }
+    instruction
+}
+
+/// Decodes the operands that follow an opcode, returning them together with
+/// the number of bytes read.
+pub fn read_operands(definition: &Definition, instructions: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(definition.operand_widths.len());
+    let mut offset = 0;
+    for width in definition.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&instructions[offset..]) as usize),
+            1 => operands.push(instructions[offset] as usize),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+        offset += width;
+    }
+    (operands, offset)
+}
+
+pub fn read_u16(instructions: &[u8]) -> u16 {
+    u16::from_be_bytes([instructions[0], instructions[1]])
+}
+
+/// Renders instructions one per line as `offset name operands`.
+pub fn disassemble(instructions: &[u8]) -> String {
+    let mut output = String::new();
+    let mut offset = 0;
+    while offset < instructions.len() {
+        let op = match Opcode::try_from(instructions[offset]) {
+            Ok(op) => op,
+            Err(byte) => {
+                let _ = writeln!(output, "ERROR: unknown opcode {}", byte);
+                offset += 1;
+                continue;
+            }
+        };
+        let definition = op.definition();
+        let (operands, read) = read_operands(&definition, &instructions[offset + 1..]);
+        let _ = write!(output, "{:04} {}", offset, definition.name);
+        for operand in operands {
+            let _ = write!(output, " {}", operand);
+        }
+        output.push('\n');
+        offset += 1 + read;
+    }
+    output
+}
+
+/// Maps instruction offsets back to the source they were compiled from.
+/// Only instructions that can fail at runtime are recorded, in the order
+/// they were emitted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceMap {
+    entries: Vec<(usize, Span)>,
+}
+
+impl SourceMap {
+    pub fn insert(&mut self, offset: usize, span: Span) {
+        self.entries.push((offset, span));
+    }
+
+    pub fn get(&self, offset: usize) -> Span {
+        match self.entries.binary_search_by_key(&offset, |(offset, _)| *offset) {
+            Ok(index) => self.entries[index].1,
+            Err(_) => Span::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod code_tester {
+    use super::{disassemble, make, read_operands, Opcode, OPCODES};
+
+    #[test]
+    fn test_make() {
+        let tests = vec![
+            (Opcode::Constant, vec![65534], vec![Opcode::Constant as u8, 255, 254]),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+            (Opcode::GetLocal, vec![255], vec![Opcode::GetLocal as u8, 255]),
+            (Opcode::Closure, vec![65534, 255], vec![Opcode::Closure as u8, 255, 254, 255]),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(make(op, &operands), expected, "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests = vec![
+            (Opcode::Constant, vec![65535], 2),
+            (Opcode::GetLocal, vec![255], 1),
+            (Opcode::Closure, vec![65535, 255], 3),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = make(op, &operands);
+            let (read, n) = read_operands(&op.definition(), &instruction[1..]);
+            assert_eq!(n, bytes_read);
+            assert_eq!(read, operands);
+        }
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let instructions = [
+            make(Opcode::Add, &[]),
+            make(Opcode::GetLocal, &[1]),
+            make(Opcode::Constant, &[2]),
+            make(Opcode::Constant, &[65535]),
+            make(Opcode::Closure, &[65535, 255]),
+        ]
+        .concat();
+
+        let expected = "0000 OpAdd
+0001 OpGetLocal 1
+0003 OpConstant 2
+0006 OpConstant 65535
+0009 OpClosure 65535 255
+";
+        assert_eq!(disassemble(&instructions), expected);
+    }
+
+    #[test]
+    fn test_opcode_bytes() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(*op as u8 as usize, byte);
+            assert_eq!(Opcode::try_from(byte as u8), Ok(*op));
+        }
+        assert_eq!(Opcode::try_from(OPCODES.len() as u8), Err(OPCODES.len() as u8));
+    }
+}
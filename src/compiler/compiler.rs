@@ -0,0 +1,676 @@
+use std::{collections::BTreeSet, error, fmt, mem, rc::Rc};
+
+use crate::{
+    ast::ast::{BlockStatement, Expression, Identifier, Program, Statement, Statements},
+    code::code::{make, Instructions, Opcode, SourceMap},
+    compiler::symbol_table::{Symbol, SymbolScope, SymbolTable},
+    object::object::{CompiledFunction, Object},
+    token::token::{Span, Tokens},
+};
+
+/// The output of compiling a program: the top-level instructions and
+/// everything the VM needs to run them.
+#[derive(Debug)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub source_map: SourceMap,
+    pub constants: Vec<Object>,
+    pub global_names: Vec<String>,
+}
+
+/// A program that parses but does not fit the bytecode's limits, such as a
+/// function with more than 256 locals.
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct CompileError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for CompileError {}
+
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    source_map: SourceMap,
+}
+
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            constants: Vec::new(),
+            symbol_table: SymbolTable::new(),
+            scopes: vec![CompilationScope::default()],
+        }
+    }
+
+    pub fn compile(&mut self, program: &Program) -> Result<(), CompileError> {
+        for statement in program {
+            self.compile_statement(statement)?;
+        }
+        // Like the evaluator, a program ending in `let` produces null.
+        if !matches!(program.last(), Some(Statement::ExpressionStatement(_))) {
+            self.emit(Opcode::Null, &[]);
+            self.emit(Opcode::Pop, &[]);
+        }
+        Ok(())
+    }
+
+    pub fn bytecode(mut self) -> Bytecode {
+        let scope = self.scopes.pop().unwrap_or_default();
+        Bytecode {
+            instructions: scope.instructions,
+            source_map: scope.source_map,
+            constants: self.constants,
+            global_names: self.symbol_table.global_names(),
+        }
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        match statement {
+            Statement::ExpressionStatement(expression) => {
+                self.compile_expression(expression)?;
+                self.emit(Opcode::Pop, &[]);
+            }
+            Statement::ReturnStatement(_, value) => {
+                self.compile_expression(value)?;
+                self.emit(Opcode::ReturnValue, &[]);
+            }
+            Statement::LetStatement(identifier, value) => {
+                // The name is bound after the value is compiled, so
+                // `let x = x;` reads the outer `x`, if any. Cells exist from
+                // the start of the function but stay empty until then.
+                self.compile_expression(value)?;
+                let symbol = self.symbol_table.define(&identifier.value);
+                match symbol.scope {
+                    SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index]),
+                    SymbolScope::Cell => self.emit(Opcode::SetCell, &[symbol.index]),
+                    _ => {
+                        check_limit(symbol.index + 1, u8::MAX as usize + 1, "local bindings", identifier.token.span)?;
+                        self.emit(Opcode::SetLocal, &[symbol.index])
+                    }
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles a block so that it leaves its value on the stack: the value
+    /// of its last expression statement, or null.
+    fn compile_block_value(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        match block.statements.split_last() {
+            Some((Statement::ExpressionStatement(last), rest)) => {
+                self.compile_statements(rest)?;
+                self.compile_expression(last)
+            }
+            _ => {
+                self.compile_statements(&block.statements)?;
+                self.emit(Opcode::Null, &[]);
+                Ok(())
+            }
+        }
+    }
+
+    fn compile_statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        for statement in statements {
+            self.compile_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), CompileError> {
+        match expression {
+            Expression::IntegerLiteral(_, value) => {
+                self.emit_constant(Object::Integer(*value), expression.span())?;
+            }
+            Expression::FloatLiteral(_, value) => {
+                self.emit_constant(Object::Float(*value), expression.span())?;
+            }
+            Expression::StringLiteral(_, value) => {
+                self.emit_constant(Object::String(value.clone()), expression.span())?;
+            }
+            Expression::Boolean(_, true) => {
+                self.emit(Opcode::True, &[]);
+            }
+            Expression::Boolean(_, false) => {
+                self.emit(Opcode::False, &[]);
+            }
+            Expression::Identifier(identifier) => self.compile_identifier(identifier)?,
+            Expression::PrefixExpression(operator, right) => {
+                self.compile_expression(right)?;
+                let op = match operator.token_type {
+                    Tokens::MINUS => Opcode::Minus,
+                    _ => Opcode::Bang,
+                };
+                self.emit_with_span(op, &[], expression.span());
+            }
+            Expression::InfixExpression(left, operator, right) => match operator.token_type {
+                Tokens::AND => {
+                    // left; JumpNotTruthy false; right; Truthy; Jump end; false: False
+                    self.compile_expression(left)?;
+                    let jump_to_false = self.emit(Opcode::JumpNotTruthy, &[0]);
+                    self.compile_expression(right)?;
+                    self.emit(Opcode::Truthy, &[]);
+                    let jump_to_end = self.emit(Opcode::Jump, &[0]);
+                    self.patch_jump(jump_to_false, expression.span())?;
+                    self.emit(Opcode::False, &[]);
+                    self.patch_jump(jump_to_end, expression.span())?;
+                }
+                Tokens::OR => {
+                    // left; JumpNotTruthy right; True; Jump end; right: right; Truthy
+                    self.compile_expression(left)?;
+                    let jump_to_right = self.emit(Opcode::JumpNotTruthy, &[0]);
+                    self.emit(Opcode::True, &[]);
+                    let jump_to_end = self.emit(Opcode::Jump, &[0]);
+                    self.patch_jump(jump_to_right, expression.span())?;
+                    self.compile_expression(right)?;
+                    self.emit(Opcode::Truthy, &[]);
+                    self.patch_jump(jump_to_end, expression.span())?;
+                }
+                token_type => {
+                    self.compile_expression(left)?;
+                    self.compile_expression(right)?;
+                    let op = match token_type {
+                        Tokens::PLUS => Opcode::Add,
+                        Tokens::MINUS => Opcode::Sub,
+                        Tokens::ASTERISK => Opcode::Mul,
+                        Tokens::SLASH => Opcode::Div,
+                        Tokens::PERCENT => Opcode::Mod,
+                        Tokens::EQ => Opcode::Equal,
+                        Tokens::NOTEQ => Opcode::NotEqual,
+                        Tokens::LT => Opcode::LessThan,
+                        Tokens::GT => Opcode::GreaterThan,
+                        Tokens::LTEQ => Opcode::LessEqual,
+                        _ => Opcode::GreaterEqual,
+                    };
+                    self.emit_with_span(op, &[], expression.span());
+                }
+            },
+            Expression::IfExpression(_, condition, consequence, alternative) => {
+                self.compile_expression(condition)?;
+                let jump_to_alternative = self.emit(Opcode::JumpNotTruthy, &[0]);
+                self.compile_block_value(consequence)?;
+                let jump_to_end = self.emit(Opcode::Jump, &[0]);
+                self.patch_jump(jump_to_alternative, expression.span())?;
+                match alternative {
+                    Some(alternative) => self.compile_block_value(alternative)?,
+                    None => {
+                        self.emit(Opcode::Null, &[]);
+                    }
+                }
+                self.patch_jump(jump_to_end, expression.span())?;
+            }
+            Expression::FunctionLiteral(_, parameters, body) => {
+                self.compile_function(expression, parameters, body)?;
+            }
+            Expression::CallExpression(callee, arguments, _) => {
+                self.compile_expression(callee)?;
+                for argument in arguments {
+                    self.compile_expression(argument)?;
+                }
+                check_limit(arguments.len(), u8::MAX as usize, "call arguments", expression.span())?;
+                // The opcode maps to the callee, for "not a function", and
+                // its operand byte to the whole call, for arity errors.
+                let position = self.emit_with_span(Opcode::Call, &[arguments.len()], callee.span());
+                self.current_scope().source_map.insert(position + 1, expression.span());
+            }
+            Expression::ArrayLiteral(_, elements, _) => {
+                for element in elements {
+                    self.compile_expression(element)?;
+                }
+                check_limit(elements.len(), u16::MAX as usize, "array elements", expression.span())?;
+                self.emit(Opcode::Array, &[elements.len()]);
+            }
+            Expression::HashLiteral(_, pairs, _) => {
+                for (key, value) in pairs {
+                    self.compile_expression(key)?;
+                    // Keys are checked as soon as they are computed, before
+                    // their value, as in the evaluator. Literals need no check.
+                    if !matches!(
+                        key,
+                        Expression::IntegerLiteral(..) | Expression::StringLiteral(..) | Expression::Boolean(..)
+                    ) {
+                        self.emit_with_span(Opcode::Hashable, &[], key.span());
+                    }
+                    self.compile_expression(value)?;
+                }
+                check_limit(pairs.len() * 2, u16::MAX as usize, "hash entries", expression.span())?;
+                self.emit(Opcode::Hash, &[pairs.len() * 2]);
+            }
+            Expression::IndexExpression(left, index, _) => {
+                self.compile_expression(left)?;
+                self.compile_expression(index)?;
+                self.emit_with_span(Opcode::Index, &[], expression.span());
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_identifier(&mut self, identifier: &Identifier) -> Result<(), CompileError> {
+        let symbol = self.symbol_table.resolve(&identifier.value);
+        self.load_symbol(&symbol, identifier.token.span)
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, span: Span) -> Result<(), CompileError> {
+        match symbol.scope {
+            SymbolScope::Global => {
+                check_limit(symbol.index, u16::MAX as usize, "global bindings", span)?;
+                self.emit_with_span(Opcode::GetGlobal, &[symbol.index], span)
+            }
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index]),
+            // Cells are empty until their `let` runs, which is an error the
+            // VM reports at the identifier, unless a global has that name.
+            SymbolScope::Cell => self.emit_with_span(Opcode::GetCell, &[symbol.index], span),
+            SymbolScope::Free => self.emit_with_span(Opcode::GetFree, &[symbol.index], span),
+        };
+        Ok(())
+    }
+
+    fn compile_function(
+        &mut self,
+        literal: &Expression,
+        parameters: &[Identifier],
+        body: &BlockStatement,
+    ) -> Result<(), CompileError> {
+        self.enter_scope();
+        for parameter in parameters {
+            self.symbol_table.define(&parameter.value);
+        }
+        // Locals that nested functions refer to live in cells the frame
+        // creates on entry, so closures see bindings made after them.
+        let cell_names: Vec<String> = captured_locals(parameters, body).into_iter().collect();
+        for name in &cell_names {
+            let parameter = parameters.iter().any(|parameter| &parameter.value == name);
+            let parameter = parameter.then(|| self.symbol_table.resolve(name));
+            let cell = self.symbol_table.define_cell(name);
+            if let Some(parameter) = parameter {
+                self.emit(Opcode::GetLocal, &[parameter.index]);
+                self.emit(Opcode::SetCell, &[cell.index]);
+            }
+        }
+        check_limit(cell_names.len(), u8::MAX as usize + 1, "captured local bindings", literal.span())?;
+        self.compile_function_body(&body.statements)?;
+
+        let free_symbols = self.symbol_table.free_symbols().to_vec();
+        let num_locals = self.symbol_table.num_definitions();
+        let scope = self.leave_scope();
+        check_limit(num_locals, u8::MAX as usize + 1, "local bindings", literal.span())?;
+        check_limit(scope.instructions.len(), u16::MAX as usize, "bytes of instructions", literal.span())?;
+        check_limit(free_symbols.len(), u8::MAX as usize, "captured variables", literal.span())?;
+
+        let num_free = free_symbols.len();
+        for symbol in &free_symbols {
+            match symbol.scope {
+                SymbolScope::Cell => self.emit(Opcode::CaptureCell, &[symbol.index]),
+                SymbolScope::Free => self.emit(Opcode::CaptureFree, &[symbol.index]),
+                scope => unreachable!("only cells are captured, not {:?}", scope),
+            };
+        }
+        let function = CompiledFunction {
+            instructions: scope.instructions,
+            source_map: scope.source_map,
+            num_locals,
+            num_parameters: parameters.len(),
+            cell_names,
+            free_names: free_symbols.into_iter().map(|symbol| symbol.name).collect(),
+            literal: literal.to_string(),
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(function)), literal.span())?;
+        self.emit(Opcode::Closure, &[index, num_free]);
+        Ok(())
+    }
+
+    /// Compiles a function body so that it always returns: the value of a
+    /// trailing expression statement, or null.
+    fn compile_function_body(&mut self, statements: &Statements) -> Result<(), CompileError> {
+        match statements.split_last() {
+            Some((Statement::ExpressionStatement(last), rest)) => {
+                self.compile_statements(rest)?;
+                self.compile_expression(last)?;
+                self.emit(Opcode::ReturnValue, &[]);
+            }
+            Some((Statement::ReturnStatement(..), _)) => self.compile_statements(statements)?,
+            _ => {
+                self.compile_statements(statements)?;
+                self.emit(Opcode::Return, &[]);
+            }
+        }
+        Ok(())
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        let outer = mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    fn leave_scope(&mut self) -> CompilationScope {
+        let table = mem::take(&mut self.symbol_table);
+        self.symbol_table = table.into_outer().unwrap_or_default();
+        self.scopes.pop().unwrap_or_default()
+    }
+
+    fn current_scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("the top-level scope is never left")
+    }
+
+    fn add_constant(&mut self, constant: Object, span: Span) -> Result<usize, CompileError> {
+        check_limit(self.constants.len(), u16::MAX as usize, "constants", span)?;
+        self.constants.push(constant);
+        Ok(self.constants.len() - 1)
+    }
+
+    fn emit_constant(&mut self, constant: Object, span: Span) -> Result<(), CompileError> {
+        let index = self.add_constant(constant, span)?;
+        self.emit(Opcode::Constant, &[index]);
+        Ok(())
+    }
+
+    /// Appends an instruction and returns its offset.
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> usize {
+        let instruction = make(op, operands);
+        let instructions = &mut self.current_scope().instructions;
+        let position = instructions.len();
+        instructions.extend_from_slice(&instruction);
+        position
+    }
+
+    /// Like `emit`, for instructions that can fail at runtime: errors they
+    /// raise point at `span`.
+    fn emit_with_span(&mut self, op: Opcode, operands: &[usize], span: Span) -> usize {
+        let position = self.emit(op, operands);
+        self.current_scope().source_map.insert(position, span);
+        position
+    }
+
+    /// Points the jump at `position` to the next instruction to be emitted.
+    fn patch_jump(&mut self, position: usize, span: Span) -> Result<(), CompileError> {
+        let instructions = &mut self.current_scope().instructions;
+        let target = instructions.len();
+        check_limit(target, u16::MAX as usize, "bytes of instructions", span)?;
+        instructions[position + 1..position + 3].copy_from_slice(&(target as u16).to_be_bytes());
+        Ok(())
+    }
+}
+
+/// The parameters and `let` names of a function that functions nested in
+/// its body refer to. A name a nested function binds itself is counted too,
+/// which only costs a cell.
+fn captured_locals(parameters: &[Identifier], body: &BlockStatement) -> BTreeSet<String> {
+    let mut names = CapturedLocals::default();
+    names.locals.extend(parameters.iter().map(|parameter| parameter.value.clone()));
+    names.statements(&body.statements);
+    names.locals.intersection(&names.mentioned).cloned().collect()
+}
+
+#[derive(Default)]
+struct CapturedLocals {
+    /// Names bound by the function itself.
+    locals: BTreeSet<String>,
+    /// Names used inside nested function literals.
+    mentioned: BTreeSet<String>,
+    nesting: usize,
+}
+
+impl CapturedLocals {
+    fn statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            match statement {
+                Statement::LetStatement(identifier, value) => {
+                    if self.nesting == 0 {
+                        self.locals.insert(identifier.value.clone());
+                    }
+                    self.expression(value);
+                }
+                Statement::ReturnStatement(_, value) | Statement::ExpressionStatement(value) => self.expression(value),
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Identifier(identifier) => {
+                if self.nesting > 0 {
+                    self.mentioned.insert(identifier.value.clone());
+                }
+            }
+            Expression::IntegerLiteral(..)
+            | Expression::FloatLiteral(..)
+            | Expression::StringLiteral(..)
+            | Expression::Boolean(..) => {}
+            Expression::PrefixExpression(_, right) => self.expression(right),
+            Expression::InfixExpression(left, _, right) | Expression::IndexExpression(left, right, _) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::IfExpression(_, condition, consequence, alternative) => {
+                self.expression(condition);
+                self.statements(&consequence.statements);
+                if let Some(alternative) = alternative {
+                    self.statements(&alternative.statements);
+                }
+            }
+            Expression::FunctionLiteral(_, _, body) => {
+                self.nesting += 1;
+                self.statements(&body.statements);
+                self.nesting -= 1;
+            }
+            Expression::CallExpression(function, arguments, _) => {
+                self.expression(function);
+                arguments.iter().for_each(|argument| self.expression(argument));
+            }
+            Expression::ArrayLiteral(_, elements, _) => elements.iter().for_each(|element| self.expression(element)),
+            Expression::HashLiteral(_, pairs, _) => {
+                for (key, value) in pairs {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+        }
+    }
+}
+
+fn check_limit(value: usize, limit: usize, what: &str, span: Span) -> Result<(), CompileError> {
+    if value > limit {
+        return Err(CompileError {
+            message: format!("too many {}: the limit is {}", what, limit),
+            span,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod compiler_tester {
+    use crate::code::code::disassemble;
+    use crate::lexer::lexer::Lexer;
+    use crate::object::object::Object;
+    use crate::parser::parser::Parser;
+
+    use super::{Bytecode, Compiler};
+
+    fn compile(input: &str) -> Bytecode {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+        compiler.bytecode()
+    }
+
+    fn function_instructions(constant: &Object) -> String {
+        match constant {
+            Object::CompiledFunction(function) => disassemble(&function.instructions),
+            constant => panic!("Unexpected constant {:?}", constant),
+        }
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let bytecode = compile("1 + 2; -3 % 4");
+        assert_eq!(
+            bytecode.constants,
+            vec![Object::Integer(1), Object::Integer(2), Object::Integer(3), Object::Integer(4)]
+        );
+        assert_eq!(
+            disassemble(&bytecode.instructions),
+            "0000 OpConstant 0
+0003 OpConstant 1
+0006 OpAdd
+0007 OpPop
+0008 OpConstant 2
+0011 OpMinus
+0012 OpConstant 3
+0015 OpMod
+0016 OpPop
+"
+        );
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let bytecode = compile("if (true) { 10 }; 3333;");
+        assert_eq!(
+            disassemble(&bytecode.instructions),
+            "0000 OpTrue
+0001 OpJumpNotTruthy 10
+0004 OpConstant 0
+0007 OpJump 11
+0010 OpNull
+0011 OpPop
+0012 OpConstant 1
+0015 OpPop
+"
+        );
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let bytecode = compile("true && false; true || false;");
+        assert_eq!(
+            disassemble(&bytecode.instructions),
+            "0000 OpTrue
+0001 OpJumpNotTruthy 9
+0004 OpFalse
+0005 OpTruthy
+0006 OpJump 10
+0009 OpFalse
+0010 OpPop
+0011 OpTrue
+0012 OpJumpNotTruthy 19
+0015 OpTrue
+0016 OpJump 21
+0019 OpFalse
+0020 OpTruthy
+0021 OpPop
+"
+        );
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        let bytecode = compile("let one = 1; let two = one;");
+        assert_eq!(
+            disassemble(&bytecode.instructions),
+            "0000 OpConstant 0
+0003 OpSetGlobal 0
+0006 OpGetGlobal 0
+0009 OpSetGlobal 1
+0012 OpNull
+0013 OpPop
+"
+        );
+        assert_eq!(bytecode.global_names, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn test_functions() {
+        let bytecode = compile("fn() { let a = 1; a }; fn() { }");
+        assert_eq!(
+            function_instructions(&bytecode.constants[1]),
+            "0000 OpConstant 0
+0003 OpSetLocal 0
+0005 OpGetLocal 0
+0007 OpReturnValue
+"
+        );
+        assert_eq!(function_instructions(&bytecode.constants[2]), "0000 OpReturn\n");
+        assert_eq!(bytecode.constants[1].to_string(), "fn() { let a = 1; a }");
+    }
+
+    #[test]
+    fn test_closures() {
+        let bytecode = compile("fn(a) { fn(b) { a + b } }");
+        assert_eq!(
+            function_instructions(&bytecode.constants[0]),
+            "0000 OpGetFree 0
+0002 OpGetLocal 0
+0004 OpAdd
+0005 OpReturnValue
+"
+        );
+        assert_eq!(
+            function_instructions(&bytecode.constants[1]),
+            "0000 OpGetLocal 0
+0002 OpSetCell 0
+0004 OpCaptureCell 0
+0006 OpClosure 0 1
+0010 OpReturnValue
+"
+        );
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        let bytecode = compile("fn() { let countDown = fn(x) { countDown(x - 1); }; countDown(1); }");
+        assert_eq!(
+            function_instructions(&bytecode.constants[1]),
+            "0000 OpGetFree 0
+0002 OpGetLocal 0
+0004 OpConstant 0
+0007 OpSub
+0008 OpCall 1
+0010 OpReturnValue
+"
+        );
+        assert_eq!(
+            function_instructions(&bytecode.constants[3]),
+            "0000 OpCaptureCell 0
+0002 OpClosure 1 1
+0006 OpSetCell 0
+0008 OpGetCell 0
+0010 OpConstant 2
+0013 OpCall 1
+0015 OpReturnValue
+"
+        );
+    }
+
+    #[test]
+    fn test_too_many_locals() {
+        let lets: String = (0..257).map(|i| format!("let x{} = {};", i, i)).collect();
+        let input = format!("fn() {{ {} }}", lets);
+        let mut parser = Parser::new(Lexer::new(&input));
+        let program = parser.parse_program();
+        let error = Compiler::new().compile(&program).unwrap_err();
+        assert_eq!(error.message, "too many local bindings: the limit is 256");
+        assert_eq!(&input[error.span.start..error.span.end], "x256");
+    }
+}
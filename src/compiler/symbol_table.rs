@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Copy, Clone, Eq)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    /// A local that nested functions capture, kept in a cell the frame
+    /// shares with their closures.
+    Cell,
+    Free,
+}
+
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// Resolves names to storage slots. The outermost table holds globals;
+/// every function literal gets a table of its own enclosing the one it was
+/// written in.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    store: HashMap<String, Symbol>,
+    num_definitions: usize,
+    num_cells: usize,
+    free_symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> Self {
+        Self {
+            outer: Some(Box::new(outer)),
+            ..Self::default()
+        }
+    }
+
+    pub fn into_outer(self) -> Option<SymbolTable> {
+        self.outer.map(|outer| *outer)
+    }
+
+    pub fn num_definitions(&self) -> usize {
+        self.num_definitions
+    }
+
+    pub fn num_cells(&self) -> usize {
+        self.num_cells
+    }
+
+    /// The symbols of enclosing functions this one refers to, in the order
+    /// their values are copied into its closures.
+    pub fn free_symbols(&self) -> &[Symbol] {
+        &self.free_symbols
+    }
+
+    /// Binds `name` in this table. Globals and cells keep their slot when
+    /// they are bound again; other locals always get a fresh one, so
+    /// `fn(x, x)` sees the second argument as the evaluator does.
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = if self.outer.is_some() { SymbolScope::Local } else { SymbolScope::Global };
+        if let Some(symbol) = self.store.get(name) {
+            if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Cell) {
+                return symbol.clone();
+            }
+        }
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions,
+        };
+        self.num_definitions += 1;
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    /// Binds `name` to a fresh cell. Later `define`s of the name store into
+    /// the same cell, so closures that captured it see every binding.
+    pub fn define_cell(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Cell,
+            index: self.num_cells,
+        };
+        self.num_cells += 1;
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+
+    /// Looks `name` up through the enclosing tables, turning cells of
+    /// enclosing functions into free variables of this one. A name bound
+    /// nowhere is taken to be a global defined later; reading it before then
+    /// is a runtime error, just as in the evaluator.
+    pub fn resolve(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.store.get(name) {
+            return symbol.clone();
+        }
+        match self.outer.as_mut() {
+            None => self.define(name),
+            Some(outer) => {
+                let symbol = outer.resolve(name);
+                match symbol.scope {
+                    SymbolScope::Global => symbol,
+                    _ => self.define_free(symbol),
+                }
+            }
+        }
+    }
+
+    /// Global names ordered by slot, for runtime error messages.
+    pub fn global_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.num_definitions];
+        for symbol in self.store.values() {
+            names[symbol.index] = symbol.name.clone();
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod symbol_table_tester {
+    use super::{Symbol, SymbolScope, SymbolTable};
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            scope,
+            index,
+        }
+    }
+
+    #[test]
+    fn test_define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 1));
+        assert_eq!(local.resolve("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(local.resolve("c"), symbol("c", SymbolScope::Local, 1));
+    }
+
+    #[test]
+    fn test_resolve_free() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+        let mut first = SymbolTable::new_enclosed(global);
+        first.define_cell("b");
+        let mut second = SymbolTable::new_enclosed(first);
+        second.define("c");
+
+        assert_eq!(second.resolve("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(second.resolve("b"), symbol("b", SymbolScope::Free, 0));
+        assert_eq!(second.resolve("c"), symbol("c", SymbolScope::Local, 0));
+        assert_eq!(second.free_symbols(), &[symbol("b", SymbolScope::Cell, 0)]);
+    }
+
+    #[test]
+    fn test_nested_free() {
+        let mut first = SymbolTable::new_enclosed(SymbolTable::new());
+        first.define_cell("a");
+        let second = SymbolTable::new_enclosed(first);
+        let mut third = SymbolTable::new_enclosed(second);
+
+        assert_eq!(third.resolve("a"), symbol("a", SymbolScope::Free, 0));
+        let second = third.into_outer().unwrap();
+        assert_eq!(second.free_symbols(), &[symbol("a", SymbolScope::Cell, 0)]);
+    }
+
+    #[test]
+    fn test_cells() {
+        let mut local = SymbolTable::new_enclosed(SymbolTable::new());
+        assert_eq!(local.define("x"), symbol("x", SymbolScope::Local, 0));
+        assert_eq!(local.define_cell("f"), symbol("f", SymbolScope::Cell, 0));
+        assert_eq!(local.define("f"), symbol("f", SymbolScope::Cell, 0));
+        assert_eq!(local.num_cells(), 1);
+
+        let mut inner = SymbolTable::new_enclosed(local);
+        assert_eq!(inner.resolve("f"), symbol("f", SymbolScope::Free, 0));
+        assert_eq!(inner.free_symbols(), &[symbol("f", SymbolScope::Cell, 0)]);
+    }
+
+    #[test]
+    fn test_forward_references_are_globals() {
+        let mut local = SymbolTable::new_enclosed(SymbolTable::new());
+        assert_eq!(local.resolve("later"), symbol("later", SymbolScope::Global, 0));
+
+        let mut global = local.into_outer().unwrap();
+        assert_eq!(global.define("later"), symbol("later", SymbolScope::Global, 0));
+        assert_eq!(global.global_names(), vec!["later"]);
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt::Write;
+
+use crate::{
+    compiler::compiler::CompileError,
+    object::object::Object,
+    parser::error::ParseError,
+    token::token::{Span, Tokens},
+};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Ansi,
+}
+
+/// A single error report, independent of which stage produced it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            span: None,
+            label: None,
+            notes: vec![],
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Builds the report for an evaluation result, if it is an error.
+    pub fn from_object(object: &Object) -> Option<Self> {
+        match object {
+            Object::Error(message, span) => Some(Diagnostic::new("E0100", message.clone()).with_span(*span)),
+            _ => None,
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Self {
+        Diagnostic::new("E0200", error.message.clone()).with_span(error.span)
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let diagnostic = Diagnostic::new(error_code(error), error.to_string()).with_span(error.span());
+        match error {
+            ParseError::MissingPrefixParser { found } if found.token_type == Tokens::ILLEGAL => {
+                Diagnostic::new("E0001", format!("unexpected character `{}`", found.literal))
+                    .with_span(found.span)
+                    .with_label("not valid in Monkey source")
+            }
+            ParseError::MissingPrefixParser { .. } => diagnostic.with_label("expected an expression here"),
+            ParseError::UnexpectedToken { expected, .. } => {
+                diagnostic.with_label(format!("expected {:?}", expected))
+            }
+            ParseError::UnterminatedBlock { opened, .. } => diagnostic
+                .with_label("input ends here")
+                .with_note(format!(
+                    "the block was opened at {}:{}",
+                    opened.span.line, opened.span.column
+                )),
+            ParseError::InvalidInteger { found } => diagnostic
+                .with_label("not a valid integer literal")
+                .with_note(integer_digits_note(&found.literal)),
+            ParseError::IntegerOutOfRange { .. } => diagnostic
+                .with_label("literal out of range")
+                .with_note(format!("integers must be between {} and {}", i64::MIN, i64::MAX)),
+            ParseError::FloatOutOfRange { .. } => diagnostic
+                .with_label("literal out of range")
+                .with_note(format!("floats must be between {:e} and {:e}", f64::MIN, f64::MAX)),
+            ParseError::NestedTooDeeply { .. } => diagnostic
+                .with_label("nesting limit reached here")
+                .with_note("split the expression up with `let` bindings"),
+            ParseError::UnterminatedString { found } => {
+                let quote = Span::new(found.span.start, found.span.start + 1, found.span.line, found.span.column);
+                diagnostic
+                    .with_span(quote)
+                    .with_label("string starts here")
+                    .with_note("add a closing `\"` to end the string")
+            }
+            ParseError::UnterminatedComment { found } => {
+                let opening = Span::new(found.span.start, found.span.start + 2, found.span.line, found.span.column);
+                diagnostic
+                    .with_span(opening)
+                    .with_label("comment starts here")
+                    .with_note("block comments nest, so every `/*` needs its own `*/`")
+            }
+            ParseError::InvalidEscape { .. } => diagnostic
+                .with_label("unknown escape")
+                .with_note("valid escapes are `\\n`, `\\t`, `\\\"`, `\\\\` and `\\u{...}`"),
+        }
+    }
+}
+
+fn error_code(error: &ParseError) -> &'static str {
+    match error {
+        ParseError::UnexpectedToken { .. } => "E0010",
+        ParseError::MissingPrefixParser { .. } => "E0011",
+        ParseError::UnterminatedBlock { .. } => "E0012",
+        ParseError::InvalidInteger { .. } => "E0013",
+        ParseError::NestedTooDeeply { .. } => "E0014",
+        ParseError::UnterminatedString { .. } => "E0015",
+        ParseError::InvalidEscape { .. } => "E0016",
+        ParseError::UnterminatedComment { .. } => "E0017",
+        ParseError::IntegerOutOfRange { .. } => "E0018",
+        ParseError::FloatOutOfRange { .. } => "E0019",
+    }
+}
+
+fn integer_digits_note(literal: &str) -> &'static str {
+    match literal.get(..2) {
+        Some("0x") => "hexadecimal literals take the digits 0-9, a-f and A-F after `0x`",
+        Some("0o") => "octal literals take the digits 0-7 after `0o`",
+        Some("0b") => "binary literals take the digits 0 and 1 after `0b`",
+        _ => "decimal literals take the digits 0-9",
+    }
+}
+
+/// Renders every diagnostic as a multi-line report pointing into `source`.
+/// `name` identifies the source in the `-->` line, e.g. a file path.
+pub fn render(name: &str, source: &str, diagnostics: &[Diagnostic], style: Style) -> String {
+    let mut output = String::new();
+    for diagnostic in diagnostics {
+        render_one(&mut output, name, source, diagnostic, style);
+    }
+    output
+}
+
+fn render_one(output: &mut String, name: &str, source: &str, diagnostic: &Diagnostic, style: Style) {
+    let paint = |colour: &'static str| if style == Style::Ansi { colour } else { "" };
+    let (red, blue, bold, reset) = (paint(RED), paint(BLUE), paint(BOLD), paint(RESET));
+
+    let _ = writeln!(
+        output,
+        "{}error[{}]{}{}: {}{}",
+        red, diagnostic.code, reset, bold, diagnostic.message, reset
+    );
+
+    // Lines count from 1, so a zero line is a `Span::default()` that never
+    // got a location and is rendered like a missing span.
+    let span = match diagnostic.span {
+        Some(span) if span.line > 0 => span,
+        _ => {
+            for note in &diagnostic.notes {
+                let _ = writeln!(output, "  {}= {}help{}: {}", blue, bold, reset, note);
+            }
+            return;
+        }
+    };
+
+    let line = source.lines().nth(span.line - 1).unwrap_or("");
+    let gutter = " ".repeat(span.line.to_string().len());
+    let _ = writeln!(output, "{}{}-->{} {}:{}:{}", gutter, blue, reset, name, span.line, span.column);
+    let _ = writeln!(output, "{} {}|{}", gutter, blue, reset);
+    let _ = writeln!(output, "{}{} |{} {}", blue, span.line, reset, line);
+
+    // Reuse the tabs of the source line so the carets stay aligned with it.
+    let padding: String = line
+        .chars()
+        .take(span.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let start = line.char_indices().nth(span.column.saturating_sub(1)).map_or(line.len(), |(offset, _)| offset);
+    let available = line[start..].chars().count();
+    let length = source
+        .get(span.start..span.end)
+        .map_or(1, |text| text.lines().next().unwrap_or("").chars().count())
+        .min(available)
+        .max(1);
+    let label = match &diagnostic.label {
+        Some(label) => format!(" {}", label),
+        None => String::new(),
+    };
+    let _ = writeln!(
+        output,
+        "{} {}|{} {}{}{}{}{}",
+        gutter,
+        blue,
+        reset,
+        padding,
+        red,
+        "^".repeat(length),
+        label,
+        reset
+    );
+
+    for note in &diagnostic.notes {
+        let _ = writeln!(output, "{} {}={} {}help{}: {}", gutter, blue, reset, bold, reset, note);
+    }
+}
+
+#[cfg(test)]
+mod diagnostics_tester {
+    use super::*;
+    use crate::evaluator::evaluator::eval;
+    use crate::lexer::lexer::Lexer;
+    use crate::object::environment::Environment;
+    use crate::parser::parser::Parser;
+
+    fn parse_diagnostics(source: &str) -> Vec<Diagnostic> {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse_program();
+        parser.errors().iter().map(Diagnostic::from).collect()
+    }
+
+    #[test]
+    fn renders_parse_error() {
+        let source = "let x = 5;\nlet y 10;\n";
+
+        let output = render("main.monkey", source, &parse_diagnostics(source), Style::Plain);
+
+        assert_eq!(
+            output,
+            "error[E0010]: expected next token to be ASSIGN, got INT instead
+ --> main.monkey:2:7
+  |
+2 | let y 10;
+  |       ^^ expected ASSIGN
+"
+        );
+    }
+
+    #[test]
+    fn renders_notes_and_tabs() {
+        let source = "if (x) {\n\tlet y = 1;";
+
+        let output = render("<repl>", source, &parse_diagnostics(source), Style::Plain);
+
+        assert_eq!(
+            output,
+            "error[E0012]: expected RBRACE to close block, got EOF instead
+ --> <repl>:2:12
+  |
+2 | \tlet y = 1;
+  | \t          ^ input ends here
+  = help: the block was opened at 1:8
+"
+        );
+    }
+
+    #[test]
+    fn renders_illegal_character() {
+        let source = "let a = 1 + @;";
+
+        let output = render("<repl>", source, &parse_diagnostics(source)[..1], Style::Plain);
+
+        assert!(output.starts_with("error[E0001]: unexpected character `@`\n"));
+        assert!(output.ends_with("  |             ^ not valid in Monkey source\n"));
+    }
+
+    #[test]
+    fn renders_unterminated_string() {
+        let source = "let a = 1;\nlet s = \"abc;\nlet b = 2;";
+
+        let output = render("<repl>", source, &parse_diagnostics(source), Style::Plain);
+
+        assert_eq!(
+            output,
+            "error[E0015]: unterminated string literal
+ --> <repl>:2:9
+  |
+2 | let s = \"abc;
+  |         ^ string starts here
+  = help: add a closing `\"` to end the string
+"
+        );
+    }
+
+    #[test]
+    fn renders_unterminated_comment() {
+        let source = "let a = 1; /* note\nlet b = 2;";
+
+        let output = render("<repl>", source, &parse_diagnostics(source), Style::Plain);
+
+        assert_eq!(
+            output,
+            "error[E0017]: unterminated block comment
+ --> <repl>:1:12
+  |
+1 | let a = 1; /* note
+  |            ^^ comment starts here
+  = help: block comments nest, so every `/*` needs its own `*/`
+"
+        );
+    }
+
+    #[test]
+    fn renders_integer_errors() {
+        let source = "let big = 18446744073709551616;\nlet bits = 0b0121;";
+
+        let output = render("<repl>", source, &parse_diagnostics(source), Style::Plain);
+
+        assert_eq!(
+            output,
+            "error[E0018]: integer literal 18446744073709551616 is out of range
+ --> <repl>:1:11
+  |
+1 | let big = 18446744073709551616;
+  |           ^^^^^^^^^^^^^^^^^^^^ literal out of range
+  = help: integers must be between -9223372036854775808 and 9223372036854775807
+error[E0013]: could not parse \"0b0121\" as integer
+ --> <repl>:2:12
+  |
+2 | let bits = 0b0121;
+  |            ^^^^^^ not a valid integer literal
+  = help: binary literals take the digits 0 and 1 after `0b`
+"
+        );
+    }
+
+    #[test]
+    fn renders_runtime_error() {
+        let source = "let a = 5;\na + true;";
+        let program = Parser::new(Lexer::new(source)).parse_program();
+        let result = eval(&program, &Environment::new());
+
+        let diagnostic = Diagnostic::from_object(&result).unwrap();
+        let output = render("<repl>", source, &[diagnostic], Style::Plain);
+
+        assert_eq!(
+            output,
+            "error[E0100]: type mismatch: INTEGER + BOOLEAN
+ --> <repl>:2:1
+  |
+2 | a + true;
+  | ^^^^^^^^
+"
+        );
+    }
+
+    #[test]
+    fn renders_ansi_colours() {
+        let diagnostic = Diagnostic::new("E0100", "boom").with_span(Span::new(0, 1, 1, 1));
+
+        let output = render("<repl>", "x", &[diagnostic], Style::Ansi);
+
+        assert!(output.starts_with("\x1b[1;31merror[E0100]\x1b[0m\x1b[1m: boom\x1b[0m\n"));
+        assert!(output.contains("\x1b[1;31m^\x1b[0m"));
+    }
+
+    #[test]
+    fn renders_without_span() {
+        let diagnostic = Diagnostic::new("E0100", "boom").with_note("try again");
+
+        let output = render("<repl>", "", &[diagnostic], Style::Plain);
+
+        assert_eq!(output, "error[E0100]: boom\n  = help: try again\n");
+    }
+
+    #[test]
+    fn renders_default_span_without_location() {
+        let result = Object::Error(String::from("stack overflow"), Span::default());
+        let diagnostic = Diagnostic::from_object(&result).unwrap().with_note("try again");
+
+        let output = render("<repl>", "f(1);", &[diagnostic], Style::Plain);
+
+        assert_eq!(output, "error[E0100]: stack overflow\n  = help: try again\n");
+    }
+}
@@ -0,0 +1,30 @@
+use crate::object::object::{Builtin, Object};
+
+/// Runs `builtin`, sending anything it prints to `print`. Both backends call
+/// this so builtins behave the same everywhere.
+pub fn call_builtin(builtin: Builtin, arguments: Vec<Object>, print: &mut dyn FnMut(&str)) -> Object {
+    match builtin {
+        Builtin::Puts => {
+            for argument in arguments {
+                print(&format!("{}\n", argument));
+            }
+            Object::Null
+        }
+    }
+}
+
+#[cfg(test)]
+mod builtins_tester {
+    use crate::object::object::{Builtin, Object};
+
+    use super::call_builtin;
+
+    #[test]
+    fn test_puts() {
+        let mut output = String::new();
+        let arguments = vec![Object::String(String::from("hi")), Object::Integer(1), Object::Null];
+        let result = call_builtin(Builtin::Puts, arguments, &mut |text| output.push_str(text));
+        assert_eq!(result, Object::Null);
+        assert_eq!(output, "hi\n1\nnull\n");
+    }
+}
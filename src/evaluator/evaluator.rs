@@ -0,0 +1,979 @@
+use std::{collections::BTreeMap, hint, rc::Rc};
+
+use crate::{
+    ast::ast::{BlockStatement, Expression, Identifier, Program, Statement},
+    evaluator::builtins::call_builtin,
+    object::{
+        environment::{Env, Environment},
+        object::{Builtin, Function, HashKey, Object},
+    },
+    token::token::{Span, Token, Tokens},
+};
+
+/// How deeply function calls may nest before evaluation stops with a
+/// "stack overflow" error. The VM allows as many frames.
+pub const MAX_CALL_DEPTH: usize = 1024;
+
+/// How much native stack nested calls may use before evaluation stops with
+/// a "stack overflow" error, even short of `MAX_CALL_DEPTH`. A call takes
+/// from about 1.5 KiB in a release build to 10 KiB or more in a debug build,
+/// depending on how deeply its body nests, so counting calls alone cannot
+/// keep within the 2 MiB a thread gets by default. Half of that is left for
+/// the caller and for the expressions of the innermost call.
+pub const STACK_BUDGET: usize = 1024 * 1024;
+
+pub fn eval(program: &Program, env: &Env) -> Object {
+    Environment::root(env).borrow_mut().set_stack_base(stack_address());
+    let result = eval_program(program, env);
+    Environment::collect_cycles(env);
+    result
+}
+
+fn eval_program(program: &Program, env: &Env) -> Object {
+    let mut result = Object::Null;
+    for statement in program {
+        result = eval_statement(statement, env);
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error(..) => return result,
+            _ => {}
+        }
+    }
+    result
+}
+
+fn eval_block_statement(block: &BlockStatement, env: &Env) -> Object {
+    let mut result = Object::Null;
+    for statement in &block.statements {
+        result = eval_statement(statement, env);
+        if matches!(result, Object::ReturnValue(_) | Object::Error(..)) {
+            return result;
+        }
+    }
+    result
+}
+
+fn eval_statement(statement: &Statement, env: &Env) -> Object {
+    match statement {
+        Statement::ExpressionStatement(expression) => eval_expression(expression, env),
+        Statement::ReturnStatement(_, expression) => {
+            let value = eval_expression(expression, env);
+            if value.is_error() {
+                return value;
+            }
+            Object::ReturnValue(Box::new(value))
+        }
+        Statement::LetStatement(identifier, expression) => {
+            let value = eval_expression(expression, env);
+            if value.is_error() {
+                return value;
+            }
+            env.borrow_mut().set(identifier.value.clone(), value);
+            Object::Null
+        }
+    }
+}
+
+// Evaluation recurses through this function for every level of the tree, so
+// anything beyond dispatching lives in helpers that only take stack space
+// while their kind of expression is being evaluated.
+fn eval_expression(expression: &Expression, env: &Env) -> Object {
+    match expression {
+        Expression::IntegerLiteral(_, value) => Object::Integer(*value),
+        Expression::FloatLiteral(_, value) => Object::Float(*value),
+        Expression::StringLiteral(_, value) => Object::String(value.clone()),
+        Expression::Boolean(_, value) => Object::Boolean(*value),
+        Expression::Identifier(identifier) => eval_identifier(identifier, env),
+        Expression::PrefixExpression(operator, right) => {
+            let right = eval_expression(right, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_prefix_expression(operator, right, expression.span())
+        }
+        Expression::InfixExpression(left, operator, right) => {
+            eval_infix(left, operator, right, expression.span(), env)
+        }
+        Expression::IfExpression(_, condition, consequence, alternative) => {
+            eval_if_expression(condition, consequence, alternative.as_ref(), env)
+        }
+        Expression::FunctionLiteral(_, parameters, body) => eval_function_literal(parameters, body, env),
+        Expression::CallExpression(callee, arguments, _) => {
+            eval_call_expression(callee, arguments, expression.span(), env)
+        }
+        Expression::ArrayLiteral(_, elements, _) => match eval_expressions(elements, env) {
+            Ok(elements) => Object::Array(Rc::new(elements)),
+            Err(error) => error,
+        },
+        Expression::IndexExpression(left, index, _) => eval_index(left, index, expression.span(), env),
+        Expression::HashLiteral(_, pairs, _) => eval_hash_literal(pairs, env),
+    }
+}
+
+#[inline(never)]
+fn eval_identifier(identifier: &Identifier, env: &Env) -> Object {
+    if let Some(value) = env.borrow().get(&identifier.value) {
+        return value;
+    }
+    match Builtin::lookup(&identifier.value) {
+        Some(builtin) => Object::Builtin(builtin),
+        None => Object::Error(format!("identifier not found: {}", identifier.value), identifier.token.span),
+    }
+}
+
+#[inline(never)]
+fn eval_infix(left: &Expression, operator: &Token, right: &Expression, span: Span, env: &Env) -> Object {
+    let left = eval_expression(left, env);
+    if left.is_error() {
+        return left;
+    }
+    // `&&` and `||` only evaluate their right operand when the left
+    // one does not already decide the result.
+    match (operator.token_type, left.is_truthy()) {
+        (Tokens::AND, false) => return Object::Boolean(false),
+        (Tokens::OR, true) => return Object::Boolean(true),
+        (Tokens::AND | Tokens::OR, _) => {
+            let right = eval_expression(right, env);
+            if right.is_error() {
+                return right;
+            }
+            return Object::Boolean(right.is_truthy());
+        }
+        _ => {}
+    }
+    let right = eval_expression(right, env);
+    if right.is_error() {
+        return right;
+    }
+    eval_infix_expression(operator, left, right, span)
+}
+
+#[inline(never)]
+fn eval_if_expression(
+    condition: &Expression,
+    consequence: &BlockStatement,
+    alternative: Option<&BlockStatement>,
+    env: &Env,
+) -> Object {
+    let condition = eval_expression(condition, env);
+    if condition.is_error() {
+        return condition;
+    }
+    match (condition.is_truthy(), alternative) {
+        (true, _) => eval_block_statement(consequence, env),
+        (false, Some(alternative)) => eval_block_statement(alternative, env),
+        (false, None) => Object::Null,
+    }
+}
+
+#[inline(never)]
+fn eval_function_literal(parameters: &[Identifier], body: &BlockStatement, env: &Env) -> Object {
+    Object::Function(Rc::new(Function {
+        parameters: parameters.to_vec(),
+        body: body.clone(),
+        env: if env.borrow().is_global() { None } else { Some(Rc::clone(env)) },
+    }))
+}
+
+#[inline(never)]
+fn eval_call_expression(callee: &Expression, arguments: &[Expression], span: Span, env: &Env) -> Object {
+    let function = eval_expression(callee, env);
+    if function.is_error() {
+        return function;
+    }
+    let values = match eval_expressions(arguments, env) {
+        Ok(values) => values,
+        Err(error) => return error,
+    };
+    apply_function(function, values, env, callee.span(), span)
+}
+
+#[inline(never)]
+fn eval_index(left: &Expression, index: &Expression, span: Span, env: &Env) -> Object {
+    let left = eval_expression(left, env);
+    if left.is_error() {
+        return left;
+    }
+    let index = eval_expression(index, env);
+    if index.is_error() {
+        return index;
+    }
+    eval_index_expression(left, index, span)
+}
+
+#[inline(never)]
+fn eval_hash_literal(pairs: &[(Expression, Expression)], env: &Env) -> Object {
+    let mut hash = BTreeMap::new();
+    for (key_expression, value) in pairs {
+        let key = eval_expression(key_expression, env);
+        if key.is_error() {
+            return key;
+        }
+        let key = match HashKey::from_object(&key) {
+            Some(key) => key,
+            None => {
+                return Object::Error(
+                    format!("unusable as hash key: {}", key.type_name()),
+                    key_expression.span(),
+                )
+            }
+        };
+        let value = eval_expression(value, env);
+        if value.is_error() {
+            return value;
+        }
+        hash.insert(key, value);
+    }
+    Object::Hash(Rc::new(hash))
+}
+
+/// Evaluates `expressions` left to right, stopping at the first error.
+fn eval_expressions(expressions: &[Expression], env: &Env) -> Result<Vec<Object>, Object> {
+    let mut values = Vec::with_capacity(expressions.len());
+    for expression in expressions {
+        let value = eval_expression(expression, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn apply_function(function: Object, arguments: Vec<Object>, env: &Env, callee: Span, call: Span) -> Object {
+    let function = match function {
+        Object::Function(function) => function,
+        Object::Builtin(builtin) => {
+            return call_builtin(builtin, arguments, &mut |text| env.borrow().print(text));
+        }
+        other => return Object::Error(format!("not a function: {}", other.type_name()), callee),
+    };
+    if function.parameters.len() != arguments.len() {
+        return Object::Error(format!(
+            "wrong number of arguments: want={}, got={}",
+            function.parameters.len(),
+            arguments.len()
+        ), call);
+    }
+
+    let depth = env.borrow().depth() + 1;
+    let root = Environment::root(env);
+    let used = root.borrow().stack_base().abs_diff(stack_address());
+    if depth > MAX_CALL_DEPTH || used > STACK_BUDGET {
+        return Object::Error(String::from("stack overflow"), call);
+    }
+    let call_env = Environment::new_call_scope(function.env.clone(), &root, depth);
+    {
+        let mut call_env = call_env.borrow_mut();
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            call_env.set(parameter.value.clone(), argument);
+        }
+    }
+
+    let result = match eval_block_statement(&function.body, &call_env) {
+        Object::ReturnValue(value) => *value,
+        result => result,
+    };
+    // Local functions stored in the scope point back at it, so a scope
+    // that outlives its call may be kept alive by nothing but that cycle.
+    let scope = Rc::downgrade(&call_env);
+    drop(call_env);
+    if scope.strong_count() > 0 {
+        Environment::retain(env, scope);
+    }
+    result
+}
+
+/// An address in the current stack frame, to measure how much stack the
+/// calls since `eval` started have used.
+fn stack_address() -> usize {
+    let marker = 0u8;
+    hint::black_box(&marker) as *const u8 as usize
+}
+
+pub(crate) fn eval_index_expression(left: Object, index: Object, span: Span) -> Object {
+    match (left, index) {
+        (Object::Array(elements), Object::Integer(index)) => usize::try_from(index)
+            .ok()
+            .and_then(|index| elements.get(index).cloned())
+            .unwrap_or(Object::Null),
+        (Object::Hash(pairs), index) => match HashKey::from_object(&index) {
+            Some(key) => pairs.get(&key).cloned().unwrap_or(Object::Null),
+            None => Object::Error(format!("unusable as hash key: {}", index.type_name()), span),
+        },
+        (left, _) => Object::Error(format!("index operator not supported: {}", left.type_name()), span),
+    }
+}
+
+pub(crate) fn eval_prefix_expression(operator: &Token, right: Object, span: Span) -> Object {
+    match (operator.token_type, right) {
+        (Tokens::BANG, right) => Object::Boolean(!right.is_truthy()),
+        (Tokens::MINUS, Object::Integer(value)) => match value.checked_neg() {
+            Some(value) => Object::Integer(value),
+            None => Object::Error(format!("integer overflow: -{}", value), span),
+        },
+        (Tokens::MINUS, Object::Float(value)) => Object::Float(-value),
+        (_, right) => Object::Error(format!("unknown operator: {}{}", operator.literal, right.type_name()), span),
+    }
+}
+
+pub(crate) fn eval_infix_expression(operator: &Token, left: Object, right: Object, span: Span) -> Object {
+    match (left, right) {
+        (Object::Integer(left), Object::Integer(right)) => {
+            eval_integer_infix_expression(operator, left, right, span)
+        }
+        // Mixing an integer with a float promotes the integer to a float.
+        (Object::Float(left), Object::Float(right)) => eval_float_infix_expression(operator, left, right, span),
+        (Object::Integer(left), Object::Float(right)) => {
+            eval_float_infix_expression(operator, left as f64, right, span)
+        }
+        (Object::Float(left), Object::Integer(right)) => {
+            eval_float_infix_expression(operator, left, right as f64, span)
+        }
+        (Object::String(left), Object::String(right)) => match operator.token_type {
+            Tokens::PLUS => Object::String(left + &right),
+            Tokens::EQ => Object::Boolean(left == right),
+            Tokens::NOTEQ => Object::Boolean(left != right),
+            _ => Object::Error(format!("unknown operator: STRING {} STRING", operator.literal), span),
+        },
+        (Object::Boolean(left), Object::Boolean(right)) => match operator.token_type {
+            Tokens::EQ => Object::Boolean(left == right),
+            Tokens::NOTEQ => Object::Boolean(left != right),
+            _ => Object::Error(format!("unknown operator: BOOLEAN {} BOOLEAN", operator.literal), span),
+        },
+        (left, right) if left.type_name() != right.type_name() => Object::Error(format!(
+            "type mismatch: {} {} {}",
+            left.type_name(),
+            operator.literal,
+            right.type_name()
+        ), span),
+        (left, right) => Object::Error(format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            operator.literal,
+            right.type_name()
+        ), span),
+    }
+}
+
+fn eval_integer_infix_expression(operator: &Token, left: i64, right: i64, span: Span) -> Object {
+    let arithmetic = |result: Option<i64>| match result {
+        Some(value) => Object::Integer(value),
+        None => Object::Error(format!("integer overflow: {} {} {}", left, operator.literal, right), span),
+    };
+    match operator.token_type {
+        Tokens::PLUS => arithmetic(left.checked_add(right)),
+        Tokens::MINUS => arithmetic(left.checked_sub(right)),
+        Tokens::ASTERISK => arithmetic(left.checked_mul(right)),
+        Tokens::SLASH if right == 0 => Object::Error(format!("division by zero: {} / {}", left, right), span),
+        Tokens::SLASH => arithmetic(left.checked_div(right)),
+        Tokens::PERCENT if right == 0 => Object::Error(format!("division by zero: {} % {}", left, right), span),
+        Tokens::PERCENT => arithmetic(left.checked_rem(right)),
+        Tokens::LT => Object::Boolean(left < right),
+        Tokens::GT => Object::Boolean(left > right),
+        Tokens::LTEQ => Object::Boolean(left <= right),
+        Tokens::GTEQ => Object::Boolean(left >= right),
+        Tokens::EQ => Object::Boolean(left == right),
+        Tokens::NOTEQ => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: INTEGER {} INTEGER", operator.literal), span),
+    }
+}
+
+/// Float arithmetic follows IEEE 754: dividing by zero gives an infinity
+/// (or NaN for `0.0 / 0.0` and `x % 0.0`) rather than an error, and NaN
+/// compares unequal to everything, itself included.
+fn eval_float_infix_expression(operator: &Token, left: f64, right: f64, span: Span) -> Object {
+    match operator.token_type {
+        Tokens::PLUS => Object::Float(left + right),
+        Tokens::MINUS => Object::Float(left - right),
+        Tokens::ASTERISK => Object::Float(left * right),
+        Tokens::SLASH => Object::Float(left / right),
+        Tokens::PERCENT => Object::Float(left % right),
+        Tokens::LT => Object::Boolean(left < right),
+        Tokens::GT => Object::Boolean(left > right),
+        Tokens::LTEQ => Object::Boolean(left <= right),
+        Tokens::GTEQ => Object::Boolean(left >= right),
+        Tokens::EQ => Object::Boolean(left == right),
+        Tokens::NOTEQ => Object::Boolean(left != right),
+        _ => Object::Error(format!("unknown operator: FLOAT {} FLOAT", operator.literal), span),
+    }
+}
+
+#[cfg(test)]
+mod evaluator_tester {
+    use std::{collections::BTreeMap, rc::Rc};
+
+    use crate::lexer::lexer::Lexer;
+    use crate::object::environment::Environment;
+    use crate::object::object::{HashKey, Object};
+    use crate::parser::parser::{Parser, DEFAULT_MAX_DEPTH};
+    use crate::token::token::Span;
+
+    use super::eval;
+
+    fn test_eval(input: &str) -> Object {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        eval(&program, &Environment::new())
+    }
+
+    #[test]
+    fn eval_integer_expression() {
+        let tests = vec![
+            ("5;", 5),
+            ("10;", 10),
+            ("-5;", -5),
+            ("-10;", -10),
+            ("5 + 5 + 5 + 5 - 10;", 10),
+            ("2 * 2 * 2 * 2 * 2;", 32),
+            ("-50 + 100 + -50;", 0),
+            ("5 * 2 + 10;", 20),
+            ("5 + 2 * 10;", 25),
+            ("20 + 2 * -10;", 0),
+            ("50 / 2 * 2 + 10;", 60),
+            ("2 * (5 + 10);", 30),
+            ("3 * 3 * 3 + 10;", 37),
+            ("3 * (3 * 3) + 10;", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10;", 50),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_boolean_expression() {
+        let tests = vec![
+            ("true;", true),
+            ("false;", false),
+            ("1 < 2;", true),
+            ("1 > 2;", false),
+            ("1 < 1;", false),
+            ("1 > 1;", false),
+            ("1 == 1;", true),
+            ("1 != 1;", false),
+            ("1 == 2;", false),
+            ("1 != 2;", true),
+            ("true == true;", true),
+            ("false == false;", true),
+            ("true == false;", false),
+            ("true != false;", true),
+            ("(1 < 2) == true;", true),
+            ("(1 < 2) == false;", false),
+            ("(1 > 2) == true;", false),
+            ("(1 > 2) == false;", true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Boolean(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_bang_operator() {
+        let tests = vec![
+            ("!true;", false),
+            ("!false;", true),
+            ("!5;", false),
+            ("!!true;", true),
+            ("!!false;", false),
+            ("!!5;", true),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Boolean(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_if_else_expressions() {
+        let tests = vec![
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 < 2) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 }", Object::Null),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Object::Integer(10)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_return_statements() {
+        let tests = vec![
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10),
+            ("let f = fn(x) { if (x > 1) { if (x > 2) { return 3; } return 2; } 1 }; f(5) + f(2) + f(0);", 6),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_errors() {
+        let tests = vec![
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true;", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("return true + false; 5;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("if (10 > 1) { true + false; }", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("if (10 > 1) { if (10 > 1) { return true + false; } return 1; }", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("foobar;", "identifier not found: foobar"),
+            ("10 / 0;", "division by zero: 10 / 0"),
+            (r#""Hello" - "World";"#, "unknown operator: STRING - STRING"),
+            (r#""a" + 1;"#, "type mismatch: STRING + INTEGER"),
+            ("[1, 2][true];", "index operator not supported: ARRAY"),
+            ("5[0];", "index operator not supported: INTEGER"),
+            (r#"{"name": "Monkey"}[fn(x) { x }];"#, "unusable as hash key: FUNCTION"),
+            ("{[1]: 2};", "unusable as hash key: ARRAY"),
+            ("1.5 + true;", "type mismatch: FLOAT + BOOLEAN"),
+            ("{1.5: 1};", "unusable as hash key: FLOAT"),
+            ("[1][0.0];", "index operator not supported: ARRAY"),
+            ("-true + 1.0;", "unknown operator: -BOOLEAN"),
+            ("5 % 0;", "division by zero: 5 % 0"),
+            ("let min = -9223372036854775807 - 1; min % -1;", "integer overflow: -9223372036854775808 % -1"),
+            ("true && missing;", "identifier not found: missing"),
+            ("false || 1 / 0;", "division by zero: 1 / 0"),
+            ("\"a\" <= \"b\";", "unknown operator: STRING <= STRING"),
+            ("true >= false;", "unknown operator: BOOLEAN >= BOOLEAN"),
+            ("9223372036854775807 + 1;", "integer overflow: 9223372036854775807 + 1"),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(message, _) => assert_eq!(message, expected, "{}", input),
+                object => panic!("Unexpected object {:?} for {}", object, input),
+            }
+        }
+    }
+
+    #[test]
+    fn eval_string_expressions() {
+        let tests = vec![
+            (r#""Hello World!";"#, Object::String(String::from("Hello World!"))),
+            (r#""Hello" + " " + "World!";"#, Object::String(String::from("Hello World!"))),
+            (r#"let greet = fn(name) { "hi, " + name }; greet("\u{1F980}");"#, Object::String(String::from("hi, 🦀"))),
+            (r#""a\tb" == "a	b";"#, Object::Boolean(true)),
+            (r#""a" != "a";"#, Object::Boolean(false)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_array_literals() {
+        match test_eval("[1, 2 * 2, 3 + 3]") {
+            Object::Array(elements) => assert_eq!(
+                *elements,
+                vec![Object::Integer(1), Object::Integer(4), Object::Integer(6)]
+            ),
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+
+    #[test]
+    fn eval_index_expressions() {
+        let tests = vec![
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("[1, 2, 3][1]", Object::Integer(2)),
+            ("let i = 0; [1][i];", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1];", Object::Integer(3)),
+            ("let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];", Object::Integer(6)),
+            ("let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]", Object::Integer(2)),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Null),
+            ("let nested = fn() { let xs = [[1], [2, 3]]; fn() { xs[1][0] } }; nested()()", Object::Integer(2)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_hash_literals() {
+        let input = r#"let two = "two";
+{
+    "one": 10 - 9,
+    two: 1 + 1,
+    "thr" + "ee": 6 / 2,
+    4: 4,
+    true: 5,
+    false: 6
+}"#;
+        match test_eval(input) {
+            Object::Hash(pairs) => {
+                let expected = BTreeMap::from([
+                    (HashKey::String(String::from("one")), Object::Integer(1)),
+                    (HashKey::String(String::from("two")), Object::Integer(2)),
+                    (HashKey::String(String::from("three")), Object::Integer(3)),
+                    (HashKey::Integer(4), Object::Integer(4)),
+                    (HashKey::Boolean(true), Object::Integer(5)),
+                    (HashKey::Boolean(false), Object::Integer(6)),
+                ]);
+                assert_eq!(*pairs, expected);
+            }
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+
+    #[test]
+    fn eval_hash_index_expressions() {
+        let tests = vec![
+            (r#"{"foo": 5}["foo"]"#, Object::Integer(5)),
+            (r#"{"foo": 5}["bar"]"#, Object::Null),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Object::Integer(5)),
+            (r#"{}["foo"]"#, Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{false: 5}[false]", Object::Integer(5)),
+            (r#"{"a": 1, "a": 2}["a"]"#, Object::Integer(2)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn collections_display() {
+        let tests = vec![
+            (r#"[1, "two", [true], {"k": "v"}]"#, r#"[1, "two", [true], {"k": "v"}]"#),
+            (r#"{2: 1, "a": [], true: null}"#, r#"{2: 1, true: null, "a": []}"#),
+            ("{}", "{}"),
+        ];
+
+        for (input, expected) in tests {
+            let input = input.replace("null", "if (false) { 1 }");
+            assert_eq!(test_eval(&input).to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_float_expressions() {
+        let tests = vec![
+            ("1.5;", Object::Float(1.5)),
+            ("-2.5;", Object::Float(-2.5)),
+            ("1.5 + 2.25;", Object::Float(3.75)),
+            ("0.1 * 3 - 0.3 < 1e-9;", Object::Boolean(true)),
+            ("7 / 2;", Object::Integer(3)),
+            ("7 / 2.0;", Object::Float(3.5)),
+            ("7.0 / 2;", Object::Float(3.5)),
+            ("2 * 1.5 + 1;", Object::Float(4.0)),
+            ("1 == 1.0;", Object::Boolean(true)),
+            ("1.0 != 1;", Object::Boolean(false)),
+            ("2 > 1.5;", Object::Boolean(true)),
+            ("1.5 < 1;", Object::Boolean(false)),
+            ("1.0 / 0;", Object::Float(f64::INFINITY)),
+            ("-1 / 0.0;", Object::Float(f64::NEG_INFINITY)),
+            ("1.7976931348623157e308 * 10 == 1.0 / 0;", Object::Boolean(true)),
+            ("let nan = 0.0 / 0; nan == nan;", Object::Boolean(false)),
+            ("let nan = 0.0 / 0; nan != nan;", Object::Boolean(true)),
+            ("let nan = 0.0 / 0; nan < 1 == nan > 1;", Object::Boolean(true)),
+            ("if (0.0) { 1 } else { 2 };", Object::Integer(1)),
+            ("!0.0;", Object::Boolean(false)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+
+        match test_eval("0.0 / 0.0;") {
+            Object::Float(value) => assert!(value.is_nan()),
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+
+    #[test]
+    fn float_display() {
+        let tests = vec![
+            ("1.0;", "1.0"),
+            ("2.5 * 2;", "5.0"),
+            ("1e21;", "1e21"),
+            ("1.0 / 0;", "inf"),
+            ("0.0 / 0;", "NaN"),
+            ("[1, 1.0];", "[1, 1.0]"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_comparison_and_modulo() {
+        let tests = vec![
+            ("1 <= 2;", Object::Boolean(true)),
+            ("2 <= 2;", Object::Boolean(true)),
+            ("3 <= 2;", Object::Boolean(false)),
+            ("1 >= 2;", Object::Boolean(false)),
+            ("2 >= 2;", Object::Boolean(true)),
+            ("2.5 >= 2;", Object::Boolean(true)),
+            ("2 <= 1.5;", Object::Boolean(false)),
+            ("7 % 3;", Object::Integer(1)),
+            ("-7 % 3;", Object::Integer(-1)),
+            ("7 % -3;", Object::Integer(1)),
+            ("2 + 7 % 3 * 2;", Object::Integer(4)),
+            ("7.5 % 2;", Object::Float(1.5)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+
+        match test_eval("1.0 % 0;") {
+            Object::Float(value) => assert!(value.is_nan()),
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+
+    #[test]
+    fn eval_logical_operators() {
+        let tests = vec![
+            ("true && true;", Object::Boolean(true)),
+            ("true && false;", Object::Boolean(false)),
+            ("false || true;", Object::Boolean(true)),
+            ("false || false;", Object::Boolean(false)),
+            ("1 < 2 && 2 < 3;", Object::Boolean(true)),
+            ("1 && \"yes\";", Object::Boolean(true)),
+            ("if (false) { 1 } || 0;", Object::Boolean(true)),
+            // The right operand is not evaluated when the left one decides.
+            ("false && missing;", Object::Boolean(false)),
+            ("true || 1 / 0;", Object::Boolean(true)),
+            ("let calls = fn(x) { x / 0 }; false && calls(1);", Object::Boolean(false)),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_let_statements() {
+        let tests = vec![
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_unicode_identifiers() {
+        let input = "let größe = 2; let x1 = 3; let 合计 = fn(a_1, b_2) { a_1 * b_2 }; 合计(größe, x1);";
+
+        assert_eq!(test_eval(input), Object::Integer(6));
+    }
+
+    #[test]
+    fn eval_function_object() {
+        match test_eval("fn(x) { x + 2; };") {
+            Object::Function(function) => {
+                assert_eq!(function.parameters.len(), 1);
+                assert_eq!(function.parameters[0].value, "x");
+                assert_eq!(function.to_string(), "fn(x) { (x + 2) }");
+            }
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+
+    #[test]
+    fn eval_function_application() {
+        let tests = vec![
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5);", 5),
+            ("let f = fn() { return 1; 2; }; f() + 10;", 11),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_closures() {
+        let tests = vec![
+            ("let newAdder = fn(x) { fn(y) { x + y }; }; let addTwo = newAdder(2); addTwo(2);", 4),
+            ("let adder = fn(x) { fn(y) { x + y } }; adder(3)(4);", 7),
+            ("let add = fn(a, b) { a + b }; let applyFunc = fn(a, b, func) { func(a, b) }; applyFunc(2, 2, add);", 4),
+            ("let x = 10; let f = fn(x) { fn() { x } }; f(1)() + x;", 11),
+            ("let curry = fn(a) { fn(b) { fn(c) { a * b + c } } }; curry(2)(3)(4);", 10),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_recursive_functions() {
+        let tests = vec![
+            ("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);", 610),
+            ("let f = fn(n) { let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count(n) }; f(10);", 10),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_function_errors() {
+        let tests = vec![
+            ("let f = fn(x) { x }; f(1, 2);", "wrong number of arguments: want=1, got=2"),
+            ("let x = 5; x(1);", "not a function: INTEGER"),
+            ("let f = fn() { y }; f();", "identifier not found: y"),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(message, _) => assert_eq!(message, expected, "{}", input),
+                object => panic!("Unexpected object {:?} for {}", object, input),
+            }
+        }
+    }
+
+    #[test]
+    fn environments_are_released() {
+        let env = Environment::new();
+        let input = "
+            let adder = fn(x) { fn(y) { x + y } };
+            let addTwo = adder(2);
+            let compose = fn(f, g) { fn(x) { g(f(x)) } };
+            let both = compose(addTwo, adder(3));
+            both(1);
+        ";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+        assert_eq!(eval(&program, &env), Object::Integer(6));
+
+        let weak = Rc::downgrade(&env);
+        drop(env);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn local_functions_are_released() {
+        let input = "let f = fn() { let xs = [1]; let even = fn(n) { if (n == 0) { xs } else { odd(n - 1) } };
+                     let odd = fn(n) { if (n == 0) { xs } else { even(n - 1) } }; even(2) }; f();";
+        match test_eval(input) {
+            Object::Array(xs) => assert_eq!(Rc::strong_count(&xs), 1),
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+
+    #[test]
+    fn returned_recursive_functions_are_released() {
+        let make = "let make = fn(xs) { let count = fn(n) { if (n == 0) { xs } else { count(n - 1) } }; count };";
+        let tests = vec![
+            format!("{} let xs = [1]; make(xs); make(xs)(3)", make),
+            // Enough calls to collect while some of them are still running.
+            format!("{} let xs = [1]; let calls = fn(n) {{ if (n == 0) {{ make(xs)(2) }} else {{ calls(n - 1); calls(n - 1) }} }}; calls(9)", make),
+            format!("{} let xs = [1]; let count = make(xs); make(xs); count(3)", make),
+        ];
+        for input in tests {
+            match test_eval(&input) {
+                Object::Array(xs) => assert_eq!(Rc::strong_count(&xs), 1, "{}", input),
+                object => panic!("Unexpected object {:?}", object),
+            }
+        }
+
+        // A function still bound somewhere keeps its scope.
+        let env = Environment::new();
+        let program = Parser::new(Lexer::new(&format!("{} let count = make([1]); make([2]);", make))).parse_program();
+        eval(&program, &env);
+        let program = Parser::new(Lexer::new("count(3)")).parse_program();
+        assert_eq!(eval(&program, &env).to_string(), "[1]");
+    }
+
+    #[test]
+    fn eval_error_spans() {
+        let tests = vec![
+            ("5 + true;", Span::new(0, 8, 1, 1)),
+            ("let a = 1;\n-true;", Span::new(11, 16, 2, 1)),
+            ("foobar;", Span::new(0, 6, 1, 1)),
+            ("let f = fn(x) { x };\nf(1, 2);", Span::new(21, 28, 2, 1)),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(_, span) => assert_eq!(span, expected, "{}", input),
+                object => panic!("Unexpected object {:?} for {}", object, input),
+            }
+        }
+    }
+
+    #[test]
+    fn eval_puts() {
+        let output = Rc::new(std::cell::RefCell::new(String::new()));
+        let env = Environment::with_output(Rc::clone(&output));
+        let input = "let show = fn(x) { puts(x, x * 2) }; show(2); puts(\"done\", [1]);";
+        let program = Parser::new(Lexer::new(input)).parse_program();
+        assert_eq!(eval(&program, &env), Object::Null);
+        assert_eq!(*output.borrow(), "2\n4\ndone\n[1]\n");
+
+        let tests = vec![
+            ("puts;", "builtin function puts"),
+            ("let puts = 1; puts;", "1"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_long_flat_chains() {
+        assert_eq!(test_eval(&format!("{}1", "1 + ".repeat(200))), Object::Integer(201));
+
+        let input = format!("{}1", "1 + ".repeat(200_000));
+        let mut parser = Parser::new(Lexer::new(&input));
+        let program = parser.parse_program();
+        let errors: Vec<String> = parser.errors().iter().map(|error| error.to_string()).collect();
+        assert_eq!(errors, vec![format!("expression nested too deeply, the limit is {} levels", DEFAULT_MAX_DEPTH)]);
+        eval(&program, &Environment::new());
+        drop(program);
+    }
+
+    #[test]
+    fn eval_call_depth_limit() {
+        // Runs on the test harness's thread, with the default stack size.
+        let tests = vec![
+            "let g = fn(n) { if (n == 0) { 0 } else { 1 + g(n - 1) } };",
+            "let g = fn(n) { if (n == 0) { 0 } else { let m = [n - 1]; {\"a\": -(-(1 + (0 * 1 + g(m[0]))))}[\"a\"] } };",
+        ];
+        for input in tests {
+            assert_eq!(test_eval(&format!("{}\ng(50);", input)), Object::Integer(50), "{}", input);
+            match test_eval(&format!("{}\ng(5000);", input)) {
+                Object::Error(message, _) => assert_eq!(message, "stack overflow"),
+                object => panic!("Unexpected object {:?}", object),
+            }
+        }
+
+        let input = "let g = fn(n) { if (n == 0) { 0 } else { 1 + g(n - 1) } };\ng(5000);";
+        assert_eq!(test_eval(input), Object::Error(String::from("stack overflow"), Span::new(45, 53, 1, 46)));
+    }
+}
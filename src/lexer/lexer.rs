@@ -1,47 +1,87 @@
-use crate::token::token::{Token, Tokens};
+use std::borrow::Cow;
 
-pub struct Lexer {
-    input: Vec<char>,
+use crate::token::token::{Span, Token, Tokens};
+
+pub struct Lexer<'src> {
+    input: &'src str,
+    trivia: bool,
+    offset: usize,
 }
 
-impl Lexer {
-    pub fn new(value: String) -> Self {
-        Self {
-            input: value.trim().chars().collect(),
-        }
+impl<'src> Lexer<'src> {
+    pub fn new(input: &'src str) -> Self {
+        Self { input, trivia: false, offset: 0 }
+    }
+
+    /// Also yield comments, as `COMMENT` tokens, instead of skipping them.
+    /// An unterminated block comment is always yielded, as
+    /// `UNTERMINATEDCOMMENT`, so that it can be reported.
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = true;
+        self
+    }
+
+    /// Shifts the byte offsets of every span by `offset`, for input that
+    /// continues a longer source such as a REPL session. Lines and columns
+    /// still count from the start of `input`.
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
     }
 }
 
-impl IntoIterator for Lexer {
-    type Item = Token;
+impl<'src> IntoIterator for Lexer<'src> {
+    type Item = Token<'src>;
 
-    type IntoIter = LexerIterItem;
+    type IntoIter = LexerIterItem<'src>;
 
     fn into_iter(self) -> Self::IntoIter {
         LexerIterItem {
-            lexer: self,
+            input: self.input,
+            trivia: self.trivia,
+            offset: self.offset,
             index: 0,
+            line: 1,
+            column: 1,
+            column_index: 0,
         }
     }
 }
 
-pub struct LexerIterItem {
-    lexer: Lexer,
+/// Walks the source byte by byte; every token borrows its literal straight
+/// from the input. `column` is the 1-based column of the byte at
+/// `column_index`, and is brought forward lazily when a token is produced.
+pub struct LexerIterItem<'src> {
+    input: &'src str,
+    trivia: bool,
+    offset: usize,
     index: usize,
+    line: usize,
+    column: usize,
+    column_index: usize,
 }
 
-impl LexerIterItem {
-    fn read_identifier(&mut self) -> Option<Token> {
-        let in_range =
-            |a: char| -> bool { ('a'..='z').contains(&a) || ('A'..='Z').contains(&a) || a == '_' };
-        let initial_position = self.index.clone();
-        while in_range(self.lexer.input[self.index]) {
-            self.index += 1;
+impl<'src> LexerIterItem<'src> {
+    /// Identifiers follow Unicode's XID rules, with `_` also allowed as the
+    /// first character. ASCII is checked byte by byte, anything else is
+    /// decoded first.
+    fn read_identifier(&self) -> (Tokens, usize) {
+        let bytes = self.input.as_bytes();
+        let mut end = self.index;
+        while let Some(&byte) = bytes.get(end) {
+            if byte.is_ascii_alphanumeric() || byte == b'_' {
+                end += 1;
+            } else if byte.is_ascii() {
+                break;
+            } else {
+                match self.input[end..].chars().next() {
+                    Some(c) if unicode_ident::is_xid_continue(c) => end += c.len_utf8(),
+                    _ => break,
+                }
+            }
         }
-        let literal: String = self.lexer.input[initial_position..self.index]
-            .iter()
-            .collect::<String>();
-        let token_type: Tokens = match literal.as_str() {
+        let length = end - self.index;
+        let token_type: Tokens = match &self.input[self.index..end] {
             "let" => Tokens::LET,
             "fn" => Tokens::FUNCTION,
             "true" => Tokens::TRUE,
@@ -51,149 +91,231 @@ impl LexerIterItem {
             "return" => Tokens::RETURN,
             _ => Tokens::IDENT,
         };
-        Some(Token {
-            token_type,
-            literal,
-        })
+        (token_type, length)
     }
 
-    fn read_number(&mut self) -> Option<Token> {
-        let initial_position = self.index.clone();
-        while ('0'..='9').contains(&self.lexer.input[self.index]) {
-            self.index += 1;
+    /// Scans a number literal, `_` separators included. Literals with a
+    /// `0x`, `0o` or `0b` prefix take every alphanumeric character that
+    /// follows, so that a digit outside the base is reported as part of the
+    /// literal rather than starting a new token; the parser checks digits.
+    /// Decimal literals with a fraction (`1.5`) or an exponent (`2e-3`) are
+    /// FLOAT; both need a digit after the `.` or `e`.
+    fn read_number(&self) -> (Tokens, usize) {
+        let bytes = &self.input.as_bytes()[self.index..];
+        if let [b'0', b'x' | b'o' | b'b', rest @ ..] = bytes {
+            let length = rest.iter().take_while(|byte| byte.is_ascii_alphanumeric() || **byte == b'_').count();
+            return (Tokens::INT, 2 + length);
         }
-        let token = Token {
-            token_type: Tokens::INT,
-            literal: self.lexer.input[initial_position..self.index]
-                .iter()
-                .collect::<String>(),
+
+        let digits = |from: usize| {
+            bytes[from..].iter().take_while(|byte| byte.is_ascii_digit() || **byte == b'_').count()
         };
-        Some(token)
+        let is_digit = |at: usize| bytes.get(at).is_some_and(u8::is_ascii_digit);
+        let mut token_type = Tokens::INT;
+        let mut length = digits(0);
+        if bytes.get(length) == Some(&b'.') && is_digit(length + 1) {
+            token_type = Tokens::FLOAT;
+            length += 1 + digits(length + 1);
+        }
+        if matches!(bytes.get(length), Some(b'e' | b'E')) {
+            let sign = usize::from(matches!(bytes.get(length + 1), Some(b'+' | b'-')));
+            if is_digit(length + 1 + sign) {
+                token_type = Tokens::FLOAT;
+                length += 1 + sign + digits(length + 1 + sign);
+            }
+        }
+        (token_type, length)
     }
 
-    fn peek_char(&self) -> Option<char> {
-        if self.index + 1 >= self.lexer.input.len() {
-            return None;
+    /// Scans a string literal up to and including its closing quote, or to
+    /// the end of input if it has none. Escapes are only skipped here; the
+    /// parser decodes them.
+    fn read_string(&self) -> (Tokens, usize) {
+        let bytes = self.input.as_bytes();
+        let mut end = self.index + 1;
+        while end < bytes.len() {
+            match bytes[end] {
+                b'"' => return (Tokens::STRING, end + 1 - self.index),
+                b'\\' => end += 2,
+                _ => end += 1,
+            }
         }
-        Some(self.lexer.input[self.index + 1])
+        (Tokens::STRING, bytes.len() - self.index)
     }
-}
 
-impl Iterator for LexerIterItem {
-    type Item = Token;
+    fn read_line_comment(&self) -> (Tokens, usize) {
+        let length = self.input.as_bytes()[self.index..]
+            .iter()
+            .take_while(|byte| **byte != b'\n')
+            .count();
+        (Tokens::COMMENT, length)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.index == self.lexer.input.len() {
-            self.index += 1;
-            return Some(Token { token_type: Tokens::EOF, literal: String::from("") });
-        }
-        if self.index > self.lexer.input.len() {
-            return None;
+    /// Scans a block comment up to the `*/` matching its opening `/*`;
+    /// comments nest, so `/* a /* b */ c */` is a single comment.
+    fn read_block_comment(&self) -> (Tokens, usize) {
+        let bytes = self.input.as_bytes();
+        let mut depth = 0;
+        let mut end = self.index;
+        while end < bytes.len() {
+            match (bytes[end], bytes.get(end + 1)) {
+                (b'/', Some(b'*')) => {
+                    depth += 1;
+                    end += 2;
+                }
+                (b'*', Some(b'/')) => {
+                    depth -= 1;
+                    end += 2;
+                    if depth == 0 {
+                        return (Tokens::COMMENT, end - self.index);
+                    }
+                }
+                _ => end += 1,
+            }
         }
-        while char::is_whitespace(self.lexer.input[self.index]) {
-            self.index += 1
+        (Tokens::UNTERMINATEDCOMMENT, bytes.len() - self.index)
+    }
+
+    /// Moves the line counter past any newlines inside the token that
+    /// ended at `self.index`.
+    fn skip_lines(&mut self, literal: &str) {
+        if let Some(last) = literal.rfind('\n') {
+            self.line += literal.matches('\n').count();
+            self.column = 1;
+            self.column_index = self.index - literal.len() + last + 1;
         }
-        let output = match &self.lexer.input[self.index] {
-            '=' => {
-                if self.peek_char() == Some('=') {
-                    self.index += 1;
-                    Some(Token {
-                        token_type: Tokens::EQ,
-                        literal: String::from("=="),
-                    })
-                } else {
-                    Some(Token {
-                        token_type: Tokens::ASSIGN,
-                        literal: String::from('='),
-                    })
-                }
-            },
-            '+' => Some(Token {
-                token_type: Tokens::PLUS,
-                literal: String::from('+'),
-            }),
-            '(' => Some(Token {
-                token_type: Tokens::LPAREN,
-                literal: String::from('('),
-            }),
-            ')' => Some(Token {
-                token_type: Tokens::RPAREN,
-                literal: String::from(')'),
-            }),
-            '{' => Some(Token {
-                token_type: Tokens::LBRACE,
-                literal: String::from('{'),
-            }),
-            '}' => Some(Token {
-                token_type: Tokens::RBRACE,
-                literal: String::from('}'),
-            }),
-            ';' => Some(Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(';'),
-            }),
-            ',' => Some(Token {
-                token_type: Tokens::COMMA,
-                literal: String::from(','),
-            }),
-            '-' => Some(Token {
-                token_type: Tokens::MINUS,
-                literal: String::from('-'),
-            }),
-            '!' => {
-                if self.peek_char() == Some('=') {
+    }
+
+    fn current_byte(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.index).copied()
+    }
+
+    fn peek_byte(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.index + 1).copied()
+    }
+
+    fn current_char(&self) -> Option<char> {
+        self.input.get(self.index..)?.chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(byte) = self.current_byte() {
+            match byte {
+                b'\n' => {
                     self.index += 1;
-                    Some(Token {
-                        token_type: Tokens::NOTEQ,
-                        literal: String::from("!="),
-                    })
-                } else {
-                    Some(Token {
-                        token_type: Tokens::BANG,
-                        literal: String::from('!'),
-                    })
+                    self.line += 1;
+                    self.column = 1;
+                    self.column_index = self.index;
                 }
-            },
-            '/' => Some(Token {
-                token_type: Tokens::SLASH,
-                literal: String::from('/'),
-            }),
-            '*' => Some(Token {
-                token_type: Tokens::ASTERISK,
-                literal: String::from('*'),
-            }),
-            '<' => Some(Token {
-                token_type: Tokens::LT,
-                literal: String::from('<'),
-            }),
-            '>' => Some(Token {
-                token_type: Tokens::GT,
-                literal: String::from('>'),
-            }),
-            'a'..='z' | 'A'..='Z' | '_' => {
-                let ident = self.read_identifier();
-                self.index -= 1;
-                ident
+                b' ' | b'\t' | b'\r' | b'\x0b' | b'\x0c' => self.index += 1,
+                byte if !byte.is_ascii() => match self.current_char() {
+                    Some(c) if c.is_whitespace() => self.index += c.len_utf8(),
+                    _ => break,
+                },
+                _ => break,
             }
-            '0'..='9' => {
-                let number = self.read_number();
-                self.index -= 1;
-                number
+        }
+    }
+
+    fn span(&mut self, start: usize, end: usize) -> Span {
+        // Count characters rather than bytes: UTF-8 continuation bytes
+        // (0b10xx_xxxx) do not start a new column.
+        self.column += self.input.as_bytes()[self.column_index..start]
+            .iter()
+            .filter(|byte| (**byte as i8) >= -0x40)
+            .count();
+        self.column_index = start;
+        Span {
+            start: start + self.offset,
+            end: end + self.offset,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn next_token(&mut self) -> Token<'src> {
+        let start = self.index;
+        let current = match self.current_byte() {
+            Some(current) => current,
+            None => {
+                self.index += 1;
+                return Token {
+                    token_type: Tokens::EOF,
+                    literal: Cow::Borrowed(""),
+                    span: self.span(start, start),
+                };
             }
-            t => Some(Token {
-                token_type: Tokens::ILLEGAL,
-                literal: String::from(*t),
-            }),
         };
-        self.index += 1;
-        output
+        let (token_type, length) = match current {
+            b'=' if self.peek_byte() == Some(b'=') => (Tokens::EQ, 2),
+            b'=' => (Tokens::ASSIGN, 1),
+            b'+' => (Tokens::PLUS, 1),
+            b'(' => (Tokens::LPAREN, 1),
+            b')' => (Tokens::RPAREN, 1),
+            b'{' => (Tokens::LBRACE, 1),
+            b'}' => (Tokens::RBRACE, 1),
+            b'[' => (Tokens::LBRACKET, 1),
+            b']' => (Tokens::RBRACKET, 1),
+            b':' => (Tokens::COLON, 1),
+            b';' => (Tokens::SEMICOLON, 1),
+            b',' => (Tokens::COMMA, 1),
+            b'-' => (Tokens::MINUS, 1),
+            b'!' if self.peek_byte() == Some(b'=') => (Tokens::NOTEQ, 2),
+            b'!' => (Tokens::BANG, 1),
+            b'/' if self.peek_byte() == Some(b'/') => self.read_line_comment(),
+            b'/' if self.peek_byte() == Some(b'*') => self.read_block_comment(),
+            b'/' => (Tokens::SLASH, 1),
+            b'*' => (Tokens::ASTERISK, 1),
+            b'%' => (Tokens::PERCENT, 1),
+            b'<' if self.peek_byte() == Some(b'=') => (Tokens::LTEQ, 2),
+            b'<' => (Tokens::LT, 1),
+            b'>' if self.peek_byte() == Some(b'=') => (Tokens::GTEQ, 2),
+            b'>' => (Tokens::GT, 1),
+            b'&' if self.peek_byte() == Some(b'&') => (Tokens::AND, 2),
+            b'|' if self.peek_byte() == Some(b'|') => (Tokens::OR, 2),
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => self.read_identifier(),
+            b'0'..=b'9' => self.read_number(),
+            b'"' => self.read_string(),
+            _ if self.current_char().is_some_and(unicode_ident::is_xid_start) => self.read_identifier(),
+            _ => (Tokens::ILLEGAL, self.current_char().map_or(1, char::len_utf8)),
+        };
+        self.index += length;
+        let literal = &self.input[start..self.index];
+        let span = self.span(start, self.index);
+        if matches!(token_type, Tokens::STRING | Tokens::COMMENT | Tokens::UNTERMINATEDCOMMENT) {
+            self.skip_lines(literal);
+        }
+        Token { token_type, literal: Cow::Borrowed(literal), span }
+    }
+}
+
+impl<'src> Iterator for LexerIterItem<'src> {
+    type Item = Token<'src>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.index > self.input.len() {
+                return None;
+            }
+            self.skip_whitespace();
+            let token = self.next_token();
+            if token.token_type != Tokens::COMMENT || self.trivia {
+                return Some(token);
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod lexer_tester {
     use super::*;
-    use crate::token::token::Tokens;
+    use crate::token::token::{Span, Tokens};
+
+    fn assert_tokens(input: &str, expected: &[(Tokens, &str)]) {
+        let tokens: Vec<Token> = Lexer::new(input).into_iter().collect();
+        let actual: Vec<(Tokens, &str)> = tokens.iter().map(|token| (token.token_type, token.literal.as_ref())).collect();
+        assert_eq!(expected, actual, "{:?}", input);
+    }
 
     #[test]
     fn test_lexer() {
@@ -204,215 +326,64 @@ mod lexer_tester {
             x + y; 
         };
         let result = add(five, ten);
-        !-/*5;
+        !-/ *5;
 
         5 < 10 > 5;
         ";
 
-        let expected = vec![
-            Token {
-                token_type: Tokens::LET,
-                literal: String::from("let"),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("five"),
-            },
-            Token {
-                token_type: Tokens::ASSIGN,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: Tokens::INT,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::LET,
-                literal: String::from("let"),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("ten"),
-            },
-            Token {
-                token_type: Tokens::ASSIGN,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: Tokens::INT,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::LET,
-                literal: String::from("let"),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("add"),
-            },
-            Token {
-                token_type: Tokens::ASSIGN,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: Tokens::FUNCTION,
-                literal: String::from("fn"),
-            },
-            Token {
-                token_type: Tokens::LPAREN,
-                literal: String::from("("),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("x"),
-            },
-            Token {
-                token_type: Tokens::COMMA,
-                literal: String::from(","),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("y"),
-            },
-            Token {
-                token_type: Tokens::RPAREN,
-                literal: String::from(")"),
-            },
-            Token {
-                token_type: Tokens::LBRACE,
-                literal: String::from("{"),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("x"),
-            },
-            Token {
-                token_type: Tokens::PLUS,
-                literal: String::from("+"),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("y"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::RBRACE,
-                literal: String::from("}"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::LET,
-                literal: String::from("let"),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("result"),
-            },
-            Token {
-                token_type: Tokens::ASSIGN,
-                literal: String::from("="),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("add"),
-            },
-            Token {
-                token_type: Tokens::LPAREN,
-                literal: String::from("("),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("five"),
-            },
-            Token {
-                token_type: Tokens::COMMA,
-                literal: String::from(","),
-            },
-            Token {
-                token_type: Tokens::IDENT,
-                literal: String::from("ten"),
-            },
-            Token {
-                token_type: Tokens::RPAREN,
-                literal: String::from(")"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::BANG,
-                literal: String::from("!"),
-            },
-            Token {
-                token_type: Tokens::MINUS,
-                literal: String::from("-"),
-            },
-            Token {
-                token_type: Tokens::SLASH,
-                literal: String::from("/"),
-            },
-            Token {
-                token_type: Tokens::ASTERISK,
-                literal: String::from("*"),
-            },
-            Token {
-                token_type: Tokens::INT,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::INT,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: Tokens::LT,
-                literal: String::from("<"),
-            },
-            Token {
-                token_type: Tokens::INT,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: Tokens::GT,
-                literal: String::from(">"),
-            },
-            Token {
-                token_type: Tokens::INT,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::EOF,
-                literal: String::from(""),
-            },
+        let expected = [
+            (Tokens::LET, "let"),
+            (Tokens::IDENT, "five"),
+            (Tokens::ASSIGN, "="),
+            (Tokens::INT, "5"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::LET, "let"),
+            (Tokens::IDENT, "ten"),
+            (Tokens::ASSIGN, "="),
+            (Tokens::INT, "10"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::LET, "let"),
+            (Tokens::IDENT, "add"),
+            (Tokens::ASSIGN, "="),
+            (Tokens::FUNCTION, "fn"),
+            (Tokens::LPAREN, "("),
+            (Tokens::IDENT, "x"),
+            (Tokens::COMMA, ","),
+            (Tokens::IDENT, "y"),
+            (Tokens::RPAREN, ")"),
+            (Tokens::LBRACE, "{"),
+            (Tokens::IDENT, "x"),
+            (Tokens::PLUS, "+"),
+            (Tokens::IDENT, "y"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::RBRACE, "}"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::LET, "let"),
+            (Tokens::IDENT, "result"),
+            (Tokens::ASSIGN, "="),
+            (Tokens::IDENT, "add"),
+            (Tokens::LPAREN, "("),
+            (Tokens::IDENT, "five"),
+            (Tokens::COMMA, ","),
+            (Tokens::IDENT, "ten"),
+            (Tokens::RPAREN, ")"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::BANG, "!"),
+            (Tokens::MINUS, "-"),
+            (Tokens::SLASH, "/"),
+            (Tokens::ASTERISK, "*"),
+            (Tokens::INT, "5"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::INT, "5"),
+            (Tokens::LT, "<"),
+            (Tokens::INT, "10"),
+            (Tokens::GT, ">"),
+            (Tokens::INT, "5"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::EOF, ""),
         ];
 
-        let lexer = Lexer::new(String::from(input));
-
-        let actual: Vec<Token> = lexer.into_iter().collect();
-
-        assert_eq!(expected, actual);
+        assert_tokens(input, &expected);
     }
 
     #[test]
@@ -423,110 +394,384 @@ mod lexer_tester {
             return false;
         }";
 
-        let expected = vec![
-            Token {
-                token_type: Tokens::IF,
-                literal: String::from("if"),
-            },
-            Token {
-                token_type: Tokens::LPAREN,
-                literal: String::from("("),
-            },
-            Token {
-                token_type: Tokens::INT,
-                literal: String::from("5"),
-            },
-            Token {
-                token_type: Tokens::LT,
-                literal: String::from("<"),
-            },
-            Token {
-                token_type: Tokens::INT,
-                literal: String::from("10"),
-            },
-            Token {
-                token_type: Tokens::RPAREN,
-                literal: String::from(")"),
-            },
-            Token {
-                token_type: Tokens::LBRACE,
-                literal: String::from("{"),
-            },
-            Token {
-                token_type: Tokens::RETURN,
-                literal: String::from("return"),
-            },
-            Token {
-                token_type: Tokens::TRUE,
-                literal: String::from("true"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::RBRACE,
-                literal: String::from("}"),
-            },
-            Token {
-                token_type: Tokens::ELSE,
-                literal: String::from("else"),
-            },
-            Token {
-                token_type: Tokens::LBRACE,
-                literal: String::from("{"),
-            },
-            Token {
-                token_type: Tokens::RETURN,
-                literal: String::from("return"),
-            },
-            Token {
-                token_type: Tokens::FALSE,
-                literal: String::from("false"),
-            },
-            Token {
-                token_type: Tokens::SEMICOLON,
-                literal: String::from(";"),
-            },
-            Token {
-                token_type: Tokens::RBRACE,
-                literal: String::from("}"),
-            },
-            Token {
-                token_type: Tokens::EOF,
-                literal: String::from(""),
-            },
+        let expected = [
+            (Tokens::IF, "if"),
+            (Tokens::LPAREN, "("),
+            (Tokens::INT, "5"),
+            (Tokens::LT, "<"),
+            (Tokens::INT, "10"),
+            (Tokens::RPAREN, ")"),
+            (Tokens::LBRACE, "{"),
+            (Tokens::RETURN, "return"),
+            (Tokens::TRUE, "true"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::RBRACE, "}"),
+            (Tokens::ELSE, "else"),
+            (Tokens::LBRACE, "{"),
+            (Tokens::RETURN, "return"),
+            (Tokens::FALSE, "false"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::RBRACE, "}"),
+            (Tokens::EOF, ""),
         ];
 
-        let lexer = Lexer::new(String::from(input));
-
-        let actual: Vec<Token> = lexer.into_iter().collect();
-
-        assert_eq!(expected, actual);
+        assert_tokens(input, &expected);
     }
 
     #[test]
     fn test_equal() {
         let input = "== !=";
+        let expected = [
+            (Tokens::EQ, "=="),
+            (Tokens::NOTEQ, "!="),
+            (Tokens::EOF, ""),
+        ];
+
+        assert_tokens(input, &expected);
+    }
+
+    #[test]
+    fn test_brackets() {
+        let input = "[1, 2][0]; {\"a\": 1}";
+        let expected = [
+            (Tokens::LBRACKET, "["),
+            (Tokens::INT, "1"),
+            (Tokens::COMMA, ","),
+            (Tokens::INT, "2"),
+            (Tokens::RBRACKET, "]"),
+            (Tokens::LBRACKET, "["),
+            (Tokens::INT, "0"),
+            (Tokens::RBRACKET, "]"),
+            (Tokens::SEMICOLON, ";"),
+            (Tokens::LBRACE, "{"),
+            (Tokens::STRING, "\"a\""),
+            (Tokens::COLON, ":"),
+            (Tokens::INT, "1"),
+            (Tokens::RBRACE, "}"),
+            (Tokens::EOF, ""),
+        ];
+
+        assert_tokens(input, &expected);
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "// header\nlet x = 1; // trailing\n/* a /* nested */\n comment */ x / 2;\n//";
+
+        let skipped: Vec<Tokens> = Lexer::new(input).into_iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            skipped,
+            vec![
+                Tokens::LET,
+                Tokens::IDENT,
+                Tokens::ASSIGN,
+                Tokens::INT,
+                Tokens::SEMICOLON,
+                Tokens::IDENT,
+                Tokens::SLASH,
+                Tokens::INT,
+                Tokens::SEMICOLON,
+                Tokens::EOF,
+            ]
+        );
+
+        let trivia: Vec<(Tokens, String, Span)> = Lexer::new(input)
+            .with_trivia()
+            .into_iter()
+            .filter(|token| token.token_type == Tokens::COMMENT || token.token_type == Tokens::IDENT)
+            .map(|token| (token.token_type, token.literal.into_owned(), token.span))
+            .collect();
+        assert_eq!(
+            trivia,
+            vec![
+                (Tokens::COMMENT, String::from("// header"), Span::new(0, 9, 1, 1)),
+                (Tokens::IDENT, String::from("x"), Span::new(14, 15, 2, 5)),
+                (Tokens::COMMENT, String::from("// trailing"), Span::new(21, 32, 2, 12)),
+                (Tokens::COMMENT, String::from("/* a /* nested */\n comment */"), Span::new(33, 62, 3, 1)),
+                (Tokens::IDENT, String::from("x"), Span::new(63, 64, 4, 13)),
+                (Tokens::COMMENT, String::from("//"), Span::new(70, 72, 5, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let input = "let x = 1;\n/* outer /* inner */\nlet y = 2;";
+
+        let tokens: Vec<Token> = Lexer::new(input).into_iter().collect();
+
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(tokens[5].token_type, Tokens::UNTERMINATEDCOMMENT);
+        assert_eq!(tokens[5].span, Span::new(11, 42, 2, 1));
+        assert_eq!(tokens[6].span, Span::new(42, 42, 3, 11));
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let input = "x1 user_2 naïve _tmp 变量 Δt2 e\u{301}t 1abc letter lets if_ fn€";
+        let expected = [
+            (Tokens::IDENT, "x1"),
+            (Tokens::IDENT, "user_2"),
+            (Tokens::IDENT, "naïve"),
+            (Tokens::IDENT, "_tmp"),
+            (Tokens::IDENT, "变量"),
+            (Tokens::IDENT, "Δt2"),
+            (Tokens::IDENT, "e\u{301}t"),
+            (Tokens::INT, "1"),
+            (Tokens::IDENT, "abc"),
+            (Tokens::IDENT, "letter"),
+            (Tokens::IDENT, "lets"),
+            (Tokens::IDENT, "if_"),
+            (Tokens::FUNCTION, "fn"),
+            (Tokens::ILLEGAL, "€"),
+            (Tokens::EOF, ""),
+        ];
+
+        assert_tokens(input, &expected);
+    }
+
+    #[test]
+    fn test_numbers() {
+        let input = "1_000_000 0xFF_ff 0o17 0b1010 0b102 0x 0xg1 12ab 0";
+        let expected = [
+            (Tokens::INT, "1_000_000"),
+            (Tokens::INT, "0xFF_ff"),
+            (Tokens::INT, "0o17"),
+            (Tokens::INT, "0b1010"),
+            (Tokens::INT, "0b102"),
+            (Tokens::INT, "0x"),
+            (Tokens::INT, "0xg1"),
+            (Tokens::INT, "12"),
+            (Tokens::IDENT, "ab"),
+            (Tokens::INT, "0"),
+            (Tokens::EOF, ""),
+        ];
+
+        assert_tokens(input, &expected);
+    }
+
+    #[test]
+    fn test_floats() {
+        let input = "1.5 0.25e3 1e10 2E-3 6.02e+23 1_000.000_1 1. 1.x 1e 1e+ 3.14.15";
+        let expected = [
+            (Tokens::FLOAT, "1.5"),
+            (Tokens::FLOAT, "0.25e3"),
+            (Tokens::FLOAT, "1e10"),
+            (Tokens::FLOAT, "2E-3"),
+            (Tokens::FLOAT, "6.02e+23"),
+            (Tokens::FLOAT, "1_000.000_1"),
+            (Tokens::INT, "1"),
+            (Tokens::ILLEGAL, "."),
+            (Tokens::INT, "1"),
+            (Tokens::ILLEGAL, "."),
+            (Tokens::IDENT, "x"),
+            (Tokens::INT, "1"),
+            (Tokens::IDENT, "e"),
+            (Tokens::INT, "1"),
+            (Tokens::IDENT, "e"),
+            (Tokens::PLUS, "+"),
+            (Tokens::FLOAT, "3.14"),
+            (Tokens::ILLEGAL, "."),
+            (Tokens::INT, "15"),
+            (Tokens::EOF, ""),
+        ];
+
+        assert_tokens(input, &expected);
+    }
+
+    #[test]
+    fn test_comparison_and_logical_operators() {
+        let input = "a <= b >= c % d && e || f < g & h | i";
+        let expected = [
+            (Tokens::IDENT, "a"),
+            (Tokens::LTEQ, "<="),
+            (Tokens::IDENT, "b"),
+            (Tokens::GTEQ, ">="),
+            (Tokens::IDENT, "c"),
+            (Tokens::PERCENT, "%"),
+            (Tokens::IDENT, "d"),
+            (Tokens::AND, "&&"),
+            (Tokens::IDENT, "e"),
+            (Tokens::OR, "||"),
+            (Tokens::IDENT, "f"),
+            (Tokens::LT, "<"),
+            (Tokens::IDENT, "g"),
+            (Tokens::ILLEGAL, "&"),
+            (Tokens::IDENT, "h"),
+            (Tokens::ILLEGAL, "|"),
+            (Tokens::IDENT, "i"),
+            (Tokens::EOF, ""),
+        ];
+
+        assert_tokens(input, &expected);
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = "  let x = 10;\n\tx == 5;\n";
+
         let expected = vec![
-            Token {
-                token_type: Tokens::EQ,
-                literal: String::from("=="),
-            },
-            Token {
-                token_type: Tokens::NOTEQ,
-                literal: String::from("!="),
-            },
-            Token {
-                token_type: Tokens::EOF,
-                literal: String::from(""),
-            },
+            (Tokens::LET, Span::new(2, 5, 1, 3)),
+            (Tokens::IDENT, Span::new(6, 7, 1, 7)),
+            (Tokens::ASSIGN, Span::new(8, 9, 1, 9)),
+            (Tokens::INT, Span::new(10, 12, 1, 11)),
+            (Tokens::SEMICOLON, Span::new(12, 13, 1, 13)),
+            (Tokens::IDENT, Span::new(15, 16, 2, 2)),
+            (Tokens::EQ, Span::new(17, 19, 2, 4)),
+            (Tokens::INT, Span::new(20, 21, 2, 7)),
+            (Tokens::SEMICOLON, Span::new(21, 22, 2, 8)),
+            (Tokens::EOF, Span::new(23, 23, 3, 1)),
         ];
 
-        let lexer = Lexer::new(String::from(input));
+        let lexer = Lexer::new(input);
 
-        let actual: Vec<Token> = lexer.into_iter().collect();
+        let actual: Vec<(Tokens, Span)> = lexer.into_iter().map(|token| (token.token_type, token.span)).collect();
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_spans_are_byte_offsets() {
+        let input = "é + x;";
+
+        let lexer = Lexer::new(input);
+
+        let actual: Vec<(Tokens, Span)> = lexer.into_iter().map(|token| (token.token_type, token.span)).collect();
+
+        assert_eq!(actual[0], (Tokens::IDENT, Span::new(0, 2, 1, 1)));
+        assert_eq!(actual[1], (Tokens::PLUS, Span::new(3, 4, 1, 3)));
+        assert_eq!(actual[2], (Tokens::IDENT, Span::new(5, 6, 1, 5)));
+    }
+
+    #[test]
+    fn test_literals_borrow_from_input() {
+        let input = "let naïve\u{3000}= föo€;\n  bar";
+
+        let tokens: Vec<Token> = Lexer::new(input).into_iter().collect();
+
+        for token in &tokens {
+            assert!(matches!(token.literal, Cow::Borrowed(_)), "{:?} was copied", token);
+        }
+        let columns: Vec<(&str, usize, usize)> = tokens
+            .iter()
+            .map(|token| (token.literal.as_ref(), token.span.line, token.span.column))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ("let", 1, 1),
+                ("naïve", 1, 5),
+                ("=", 1, 11),
+                ("föo", 1, 13),
+                ("€", 1, 16),
+                (";", 1, 17),
+                ("bar", 2, 3),
+                ("", 2, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_input_ending_inside_tokens() {
+        let tests = vec![
+            ("let x = foo", vec![Tokens::LET, Tokens::IDENT, Tokens::ASSIGN, Tokens::IDENT, Tokens::EOF]),
+            ("x + 10", vec![Tokens::IDENT, Tokens::PLUS, Tokens::INT, Tokens::EOF]),
+            ("x =", vec![Tokens::IDENT, Tokens::ASSIGN, Tokens::EOF]),
+            ("!", vec![Tokens::BANG, Tokens::EOF]),
+            ("foo   \n\t ", vec![Tokens::IDENT, Tokens::EOF]),
+            ("", vec![Tokens::EOF]),
+            ("   ", vec![Tokens::EOF]),
+            ("\"abc", vec![Tokens::STRING, Tokens::EOF]),
+            ("\"abc\\", vec![Tokens::STRING, Tokens::EOF]),
+            ("\"\\\"", vec![Tokens::STRING, Tokens::EOF]),
+            ("\"\\é", vec![Tokens::STRING, Tokens::EOF]),
+            ("🦀", vec![Tokens::ILLEGAL, Tokens::EOF]),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+
+            let actual: Vec<Tokens> = lexer.into_iter().map(|token| token.token_type).collect();
+
+            assert_eq!(expected, actual, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_offset() {
+        let spans: Vec<Span> = Lexer::new("a\n b").with_offset(10).into_iter().map(|token| token.span).collect();
+        assert_eq!(spans, vec![Span::new(10, 11, 1, 1), Span::new(13, 14, 2, 2), Span::new(14, 14, 2, 3)]);
+    }
+
+    #[test]
+    fn test_strings() {
+        let input = "let s = \"a \\\"b\\\" \\\\\";\n\"two\nlines\" + x;\n\"open";
+
+        let actual: Vec<(Tokens, String, usize, usize)> = Lexer::new(input)
+            .into_iter()
+            .map(|token| (token.token_type, token.literal.into_owned(), token.span.line, token.span.column))
+            .collect();
+
+        assert_eq!(
+            actual,
+            vec![
+                (Tokens::LET, String::from("let"), 1, 1),
+                (Tokens::IDENT, String::from("s"), 1, 5),
+                (Tokens::ASSIGN, String::from("="), 1, 7),
+                (Tokens::STRING, String::from("\"a \\\"b\\\" \\\\\""), 1, 9),
+                (Tokens::SEMICOLON, String::from(";"), 1, 21),
+                (Tokens::STRING, String::from("\"two\nlines\""), 2, 1),
+                (Tokens::PLUS, String::from("+"), 3, 8),
+                (Tokens::IDENT, String::from("x"), 3, 10),
+                (Tokens::SEMICOLON, String::from(";"), 3, 11),
+                (Tokens::STRING, String::from("\"open"), 4, 1),
+                (Tokens::EOF, String::from(""), 4, 6),
+            ]
+        );
+    }
+
+    /// Lexes (and parses) a large number of pseudo-random inputs built from
+    /// characters the lexer cares about, plus every prefix of a real program,
+    /// checking that it never panics and always ends with a single EOF token.
+    #[test]
+    fn test_fuzz_never_panics() {
+        let alphabet: Vec<char> = "letfnifelsereturntruefalse xyz_ABC0123456789=+-!*/<>(){},;\n\t\r\"'@#$%^&|[]:.é🦀\u{0}\u{feff}"
+            .chars()
+            .collect();
+        let program = "let add = fn(x, y) { if (x < y) { return x + y; } else { !-x / 5 * y } };\nadd(1, 22) == 10 != false;";
+
+        let mut inputs: Vec<String> = (0..=program.len())
+            .filter(|end| program.is_char_boundary(*end))
+            .map(|end| String::from(&program[..end]))
+            .collect();
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..5_000 {
+            let length = (next_random(&mut state) % 40) as usize;
+            let input: String = (0..length)
+                .map(|_| alphabet[(next_random(&mut state) % alphabet.len() as u64) as usize])
+                .collect();
+            inputs.push(input);
+        }
+
+        for input in inputs {
+            let tokens: Vec<Token> = Lexer::new(&input).into_iter().collect();
+
+            assert_eq!(tokens.last().map(|token| token.token_type), Some(Tokens::EOF), "{:?}", input);
+            assert_eq!(tokens.iter().filter(|token| token.token_type == Tokens::EOF).count(), 1, "{:?}", input);
+            for token in &tokens {
+                assert!(token.span.start <= token.span.end && token.span.end <= input.len(), "{:?}", input);
+                assert_eq!(&input[token.span.start..token.span.end], token.literal, "{:?}", input);
+            }
+
+            let mut parser = crate::parser::parser::Parser::new(Lexer::new(&input));
+            parser.parse_program();
+        }
+    }
+
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
 }
@@ -1,5 +1,13 @@
+#![allow(clippy::module_inception)]
+
 pub mod token;
 pub mod lexer;
 pub mod repl;
 pub mod ast;
 pub mod parser;
+pub mod object;
+pub mod evaluator;
+pub mod diagnostics;
+pub mod code;
+pub mod compiler;
+pub mod vm;
@@ -1,8 +1,81 @@
-use std::io;
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    process,
+};
 
-use interpreter::repl::repl::repl;
+use interpreter::{
+    compiler::compiler::Compiler,
+    diagnostics::diagnostics::{render, Diagnostic, Style},
+    evaluator::evaluator::eval,
+    lexer::lexer::Lexer,
+    object::environment::Environment,
+    parser::parser::Parser,
+    repl::{interactive::interactive, protocol::protocol, repl::repl},
+    vm::vm::Vm,
+};
 
 fn main() {
-    println!("Hello, This is the Monkey programming language!");
-    repl(io::stdin().lock(), io::stdout());
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let use_vm = args.first().is_some_and(|arg| arg == "--vm");
+    if use_vm {
+        args.remove(0);
+    }
+    match args.first().map(String::as_str) {
+        // The REPL and the protocol keep a session, which only the evaluator
+        // supports.
+        Some("--json") | None if use_vm => {
+            eprintln!("--vm only runs files: interpreter --vm <file>");
+            process::exit(1);
+        }
+        Some("--json") => protocol(io::stdin().lock(), io::stdout()),
+        Some(path) => run_file(path, use_vm),
+        None => {
+            println!("Hello, This is the Monkey programming language!");
+            if io::stdin().is_terminal() {
+                if let Err(error) = interactive() {
+                    eprintln!("line editor failed: {}", error);
+                    process::exit(1);
+                }
+            } else {
+                repl(io::stdin().lock(), io::stdout());
+            }
+        }
+    }
+}
+
+/// Runs a script with the evaluator, or with the bytecode VM when `use_vm`
+/// is set. Both report errors the same way.
+fn run_file(path: &str, use_vm: bool) {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("could not read {}: {}", path, error);
+            process::exit(1);
+        }
+    };
+    let style = if io::stderr().is_terminal() { Style::Ansi } else { Style::Plain };
+
+    let mut parser = Parser::new(Lexer::new(&source));
+    let program = parser.parse_program();
+    if !parser.errors().is_empty() {
+        let diagnostics: Vec<Diagnostic> = parser.errors().iter().map(Diagnostic::from).collect();
+        eprint!("{}", render(path, &source, &diagnostics, style));
+        process::exit(1);
+    }
+
+    let result = if use_vm {
+        let mut compiler = Compiler::new();
+        if let Err(error) = compiler.compile(&program) {
+            eprint!("{}", render(path, &source, &[Diagnostic::from(&error)], style));
+            process::exit(1);
+        }
+        Vm::new(compiler.bytecode()).run()
+    } else {
+        eval(&program, &Environment::new())
+    };
+    if let Some(diagnostic) = Diagnostic::from_object(&result) {
+        eprint!("{}", render(path, &source, &[diagnostic], style));
+        process::exit(1);
+    }
 }
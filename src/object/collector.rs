@@ -0,0 +1,219 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    rc::{Rc, Weak},
+};
+
+use super::{
+    environment::{Env, Environment},
+    object::{Cell, Closure, Function, HashKey, Object},
+};
+
+/// How many retained scopes or cells may pile up before the first
+/// collection.
+const FIRST_COLLECTION: usize = 256;
+
+/// Frees values that only keep each other alive.
+///
+/// A local function is stored in the scope (or VM cell) it closes over, so
+/// once that scope outlives its call the two hold each other through `Rc`s
+/// and never reach a count of zero. Both backends hand such scopes and cells
+/// to a `Collector`, which now and then finds the ones that nothing outside
+/// the cycles refers to and empties them, letting `Rc` free the rest.
+///
+/// Finding them is trial deletion: everything reachable from the candidates
+/// is counted, the references between those values are subtracted from
+/// their strong counts, and whatever still has references left is in use
+/// from outside, along with everything it reaches.
+#[derive(Debug, Default)]
+pub struct Collector {
+    candidates: Vec<Candidate>,
+    next_collection: usize,
+}
+
+#[derive(Debug)]
+enum Candidate {
+    Scope(Weak<RefCell<Environment>>),
+    Cell(Weak<RefCell<Option<Object>>>),
+}
+
+/// A value that holds `Rc`s to other values.
+enum Node {
+    Scope(Env),
+    Cell(Cell),
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<BTreeMap<HashKey, Object>>),
+}
+
+impl Collector {
+    /// Watches a call scope that was still referenced when its call
+    /// returned.
+    pub fn retain_scope(&mut self, scope: Weak<RefCell<Environment>>) {
+        self.retain(Candidate::Scope(scope));
+    }
+
+    /// Watches a VM cell that was still referenced when its frame returned.
+    pub fn retain_cell(&mut self, cell: Weak<RefCell<Option<Object>>>) {
+        self.retain(Candidate::Cell(cell));
+    }
+
+    fn retain(&mut self, candidate: Candidate) {
+        self.candidates.push(candidate);
+        if self.candidates.len() >= self.next_collection.max(FIRST_COLLECTION) {
+            self.collect();
+        }
+    }
+
+    /// Empties every watched scope and cell that is only reachable from
+    /// other garbage, and stops watching the ones that are gone.
+    pub fn collect(&mut self) {
+        let candidates: Vec<Node> = self.candidates.drain(..).filter_map(Candidate::upgrade).collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        let mut nodes = Vec::new();
+        let mut indices = HashMap::new();
+        let mut edges: Vec<Vec<usize>> = Vec::new();
+        let mut roots = Vec::new();
+        for node in candidates {
+            roots.push(intern(node, &mut nodes, &mut indices, &mut edges));
+        }
+        let mut next = 0;
+        while next < nodes.len() {
+            for child in nodes[next].children() {
+                let child = intern(child, &mut nodes, &mut indices, &mut edges);
+                edges[next].push(child);
+            }
+            next += 1;
+        }
+
+        // Each count includes the handle in `nodes`; whatever is left after
+        // removing the references between nodes comes from outside.
+        let mut outside: Vec<usize> = nodes.iter().map(|node| node.strong_count() - 1).collect();
+        for &child in edges.iter().flatten() {
+            outside[child] -= 1;
+        }
+        let mut live = vec![false; nodes.len()];
+        let mut pending: Vec<usize> = (0..nodes.len()).filter(|&index| outside[index] > 0).collect();
+        while let Some(index) = pending.pop() {
+            if !live[index] {
+                live[index] = true;
+                pending.extend(&edges[index]);
+            }
+        }
+
+        for (node, &live) in nodes.iter().zip(&live) {
+            if !live {
+                node.clear();
+            }
+        }
+        for index in roots {
+            if live[index] {
+                self.candidates.push(nodes[index].downgrade());
+            }
+        }
+        self.next_collection = 2 * self.candidates.len();
+    }
+}
+
+/// The index of `node` in `nodes`, adding it if it is new.
+fn intern(
+    node: Node,
+    nodes: &mut Vec<Node>,
+    indices: &mut HashMap<*const (), usize>,
+    edges: &mut Vec<Vec<usize>>,
+) -> usize {
+    match indices.entry(node.address()) {
+        Entry::Occupied(entry) => *entry.get(),
+        Entry::Vacant(entry) => {
+            entry.insert(nodes.len());
+            nodes.push(node);
+            edges.push(Vec::new());
+            nodes.len() - 1
+        }
+    }
+}
+
+impl Candidate {
+    fn upgrade(self) -> Option<Node> {
+        match self {
+            Candidate::Scope(scope) => scope.upgrade().map(Node::Scope),
+            Candidate::Cell(cell) => cell.upgrade().map(Node::Cell),
+        }
+    }
+}
+
+impl Node {
+    fn of(object: &Object) -> Option<Node> {
+        match object {
+            Object::Function(function) => Some(Node::Function(Rc::clone(function))),
+            Object::Closure(closure) => Some(Node::Closure(Rc::clone(closure))),
+            Object::Array(elements) => Some(Node::Array(Rc::clone(elements))),
+            Object::Hash(pairs) => Some(Node::Hash(Rc::clone(pairs))),
+            Object::ReturnValue(value) => Node::of(value),
+            _ => None,
+        }
+    }
+
+    fn address(&self) -> *const () {
+        match self {
+            Node::Scope(scope) => Rc::as_ptr(scope).cast(),
+            Node::Cell(cell) => Rc::as_ptr(cell).cast(),
+            Node::Function(function) => Rc::as_ptr(function).cast(),
+            Node::Closure(closure) => Rc::as_ptr(closure).cast(),
+            Node::Array(elements) => Rc::as_ptr(elements).cast(),
+            Node::Hash(pairs) => Rc::as_ptr(pairs).cast(),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Scope(scope) => Rc::strong_count(scope),
+            Node::Cell(cell) => Rc::strong_count(cell),
+            Node::Function(function) => Rc::strong_count(function),
+            Node::Closure(closure) => Rc::strong_count(closure),
+            Node::Array(elements) => Rc::strong_count(elements),
+            Node::Hash(pairs) => Rc::strong_count(pairs),
+        }
+    }
+
+    /// The values this one holds a strong reference to, once per reference.
+    /// The global scope is never among them: call scopes only hold it
+    /// weakly.
+    fn children(&self) -> Vec<Node> {
+        match self {
+            Node::Scope(scope) => {
+                let scope = scope.borrow();
+                scope.values().filter_map(Node::of).chain(scope.outer().map(Node::Scope)).collect()
+            }
+            Node::Cell(cell) => cell.borrow().as_ref().and_then(Node::of).into_iter().collect(),
+            Node::Function(function) => function.env.clone().map(Node::Scope).into_iter().collect(),
+            Node::Closure(closure) => closure.free.iter().cloned().map(Node::Cell).collect(),
+            Node::Array(elements) => elements.iter().filter_map(Node::of).collect(),
+            Node::Hash(pairs) => pairs.values().filter_map(Node::of).collect(),
+        }
+    }
+
+    /// Every cycle runs through a scope or a cell, the only mutable values,
+    /// so emptying those breaks them all.
+    fn clear(&self) {
+        match self {
+            Node::Scope(scope) => scope.borrow_mut().clear(),
+            Node::Cell(cell) => {
+                cell.take();
+            }
+            _ => {}
+        }
+    }
+
+    fn downgrade(&self) -> Candidate {
+        match self {
+            Node::Scope(scope) => Candidate::Scope(Rc::downgrade(scope)),
+            Node::Cell(cell) => Candidate::Cell(Rc::downgrade(cell)),
+            _ => unreachable!("only scopes and cells are candidates"),
+        }
+    }
+}
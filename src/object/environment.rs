@@ -0,0 +1,158 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use super::{collector::Collector, object::Object};
+
+pub type Env = Rc<RefCell<Environment>>;
+
+/// A scope of bindings chained to the scope that encloses it.
+///
+/// The global scope is the root. Every function call gets a scope whose
+/// `outer` is the scope the function was defined in, so closures see later
+/// bindings of their defining scope and local functions can call each other.
+/// Call scopes reach the globals through a `Weak` link, and the global scope
+/// never stores a strong reference to itself (top-level functions carry no
+/// scope). Call scopes that outlive their call are watched by the global
+/// scope's `Collector`, which frees the ones only local functions keep
+/// alive.
+///
+/// Output from `puts` goes to stdout unless the outermost environment was
+/// created with `with_output`, in which case it is collected there.
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+    globals: Option<Weak<RefCell<Environment>>>,
+    output: Option<Rc<RefCell<String>>>,
+    depth: usize,
+    stack_base: usize,
+    collector: Collector,
+}
+
+impl Environment {
+    pub fn new() -> Env {
+        Rc::new(RefCell::new(Self::default()))
+    }
+
+    pub fn with_output(output: Rc<RefCell<String>>) -> Env {
+        let mut env = Self::default();
+        env.output = Some(output);
+        Rc::new(RefCell::new(env))
+    }
+
+    /// A scope for the body of a function call, `depth` calls deep. `outer`
+    /// is the scope the function was defined in, or `None` for a function
+    /// defined at the top level of `globals`.
+    pub fn new_call_scope(outer: Option<Env>, globals: &Env, depth: usize) -> Env {
+        let mut env = Self::default();
+        env.outer = outer;
+        env.globals = Some(Rc::downgrade(globals));
+        env.depth = depth;
+        Rc::new(RefCell::new(env))
+    }
+
+    /// Whether this is the global scope rather than the scope of a call.
+    pub fn is_global(&self) -> bool {
+        self.globals.is_none()
+    }
+
+    /// How many function calls deep this scope is; 0 at the top level.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Where the native stack stood when the running `eval` started, for
+    /// the evaluator to measure how much of it calls use.
+    pub fn stack_base(&self) -> usize {
+        self.stack_base
+    }
+
+    pub fn set_stack_base(&mut self, address: usize) {
+        self.stack_base = address;
+    }
+
+    /// Writes program output where the outermost environment sends it.
+    pub fn print(&self, text: &str) {
+        match (&self.output, self.globals()) {
+            (_, Some(globals)) => globals.borrow().print(text),
+            (Some(output), None) => output.borrow_mut().push_str(text),
+            (None, None) => print!("{}", text),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        if let Some(value) = self.store.get(name) {
+            return Some(value.clone());
+        }
+        match (&self.outer, self.globals()) {
+            (Some(outer), _) => outer.borrow().get(name),
+            (None, Some(globals)) => globals.borrow().get(name),
+            (None, None) => None,
+        }
+    }
+
+    /// The bindings of this scope, ignoring enclosing ones, sorted by name.
+    pub fn bindings(&self) -> Vec<(String, Object)> {
+        let mut bindings: Vec<(String, Object)> =
+            self.store.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+        bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+        bindings
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: Object) {
+        self.store.insert(name.into(), value);
+    }
+
+    /// Drops every binding of this scope. The collector calls this on
+    /// scopes that only reference cycles keep alive.
+    pub fn clear(&mut self) {
+        self.store.clear();
+    }
+
+    /// The scope this one is lexically nested in, unless that is the
+    /// global scope.
+    pub fn outer(&self) -> Option<Env> {
+        self.outer.clone()
+    }
+
+    /// The values bound in this scope, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &Object> {
+        self.store.values()
+    }
+
+    /// Hands a call scope that is still referenced after its call returned
+    /// to the collector of `env`'s global scope.
+    pub fn retain(env: &Env, scope: Weak<RefCell<Environment>>) {
+        Environment::root(env).borrow_mut().collector.retain_scope(scope);
+    }
+
+    /// Frees the retained call scopes that only reference cycles keep alive.
+    pub fn collect_cycles(env: &Env) {
+        Environment::root(env).borrow_mut().collector.collect();
+    }
+
+    pub fn root(env: &Env) -> Env {
+        match env.borrow().globals() {
+            Some(globals) => globals,
+            None => Rc::clone(env),
+        }
+    }
+
+    fn globals(&self) -> Option<Env> {
+        self.globals.as_ref().map(|globals| globals.upgrade().expect("global scope outlives its calls"))
+    }
+}
+
+// Functions bound in the global scope may still be in cycles with the scopes
+// they close over, so those are collected once the globals are gone.
+impl Drop for Environment {
+    fn drop(&mut self) {
+        if self.is_global() {
+            self.store.clear();
+            self.collector.collect();
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod object;
+pub mod environment;
+pub mod collector;
@@ -0,0 +1,236 @@
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc};
+
+use crate::{
+    ast::ast::{BlockStatement, Identifier},
+    code::code::{Instructions, SourceMap},
+    object::environment::Env,
+    token::token::Span,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Boolean(bool),
+    Null,
+    ReturnValue(Box<Object>),
+    Error(String, Span),
+    Function(Rc<Function>),
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<BTreeMap<HashKey, Object>>),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Builtin(Builtin),
+}
+
+/// The subset of values that can be used as hash keys. Keys are kept
+/// ordered so that hashes print the same way on every run.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+/// A function value together with the scope it closes over.
+///
+/// `env` is the call scope the literal was evaluated in, shared rather than
+/// copied, so the body sees bindings made after the function was created.
+/// Functions defined at the top level have no `env`; they find globals
+/// through the scope of whoever calls them.
+pub struct Function {
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+    pub env: Option<Env>,
+}
+
+// A local function is usually stored in the scope it closes over, so neither
+// trait may follow `env`.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        let same_env = match (&self.env, &other.env) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.parameters == other.parameters && self.body == other.body && same_env
+    }
+}
+
+/// A function provided by the interpreter. Builtins are found when a name
+/// is not bound anywhere, so programs can shadow them.
+#[derive(Debug, PartialEq, Copy, Clone, Eq)]
+pub enum Builtin {
+    Puts,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 1] = [Builtin::Puts];
+
+    pub fn lookup(name: &str) -> Option<Builtin> {
+        Builtin::ALL.into_iter().find(|builtin| builtin.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Puts => "puts",
+        }
+    }
+}
+
+/// A function lowered to bytecode. It only appears in the constant pool;
+/// the VM wraps it in a `Closure` before it can be called.
+#[derive(Debug, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub source_map: SourceMap,
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    /// Names of the locals kept in cells because nested functions capture
+    /// them, indexed like `OpGetCell` operands.
+    pub cell_names: Vec<String>,
+    /// Names of the captured variables, indexed like `OpGetFree` operands.
+    pub free_names: Vec<String>,
+    /// The printed form of the literal, so compiled functions display the
+    /// same way as the evaluator's.
+    pub literal: String,
+}
+
+/// A local variable of the VM that closures share with the frame that
+/// created them. It stays empty until the variable's `let` has run.
+pub type Cell = Rc<RefCell<Option<Object>>>;
+
+/// A compiled function together with the cells of the variables it
+/// captured from enclosing functions.
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Cell>,
+}
+
+// A cell can hold the closure that captured it, so neither trait may look
+// inside the cells.
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Closure")
+            .field("function", &self.function)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        self.function == other.function
+            && self.free.len() == other.free.len()
+            && self.free.iter().zip(&other.free).all(|(a, b)| Rc::ptr_eq(a, b))
+    }
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::String(_) => "STRING",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Error(..) => "ERROR",
+            Object::Function(..) => "FUNCTION",
+            Object::Array(..) => "ARRAY",
+            Object::Hash(..) => "HASH",
+            Object::CompiledFunction(..) => "COMPILED_FUNCTION",
+            // Closures are what the VM calls, so they share the evaluator's
+            // name for error messages.
+            Object::Closure(..) => "FUNCTION",
+            Object::Builtin(..) => "BUILTIN",
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(..))
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Null | Object::Boolean(false))
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            // Debug formatting keeps the `.0` on whole floats, so `1.0` does
+            // not print like the integer `1`.
+            Object::Float(value) => write!(f, "{:?}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::Null => write!(f, "null"),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Error(message, _) => write!(f, "ERROR: {}", message),
+            Object::Function(function) => write!(f, "{}", function),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(Object::to_element_string).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value.to_element_string()))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::CompiledFunction(function) => write!(f, "{}", function.literal),
+            Object::Closure(closure) => write!(f, "{}", closure.function.literal),
+            Object::Builtin(builtin) => write!(f, "builtin function {}", builtin.name()),
+        }
+    }
+}
+
+impl Object {
+    /// Strings nested in a collection are quoted so `["a, b"]` and
+    /// `["a", "b"]` print differently.
+    fn to_element_string(&self) -> String {
+        match self {
+            Object::String(value) => format!("{:?}", value),
+            object => object.to_string(),
+        }
+    }
+}
+
+impl HashKey {
+    pub fn from_object(object: &Object) -> Option<HashKey> {
+        match object {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(value.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashKey::Integer(value) => write!(f, "{}", value),
+            HashKey::Boolean(value) => write!(f, "{}", value),
+            HashKey::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter().map(|p| p.to_string()).collect();
+        write!(f, "fn({}) {}", parameters.join(", "), self.body)
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use crate::token::token::{Span, Token, Tokens};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedToken { expected: Tokens, found: Token<'static> },
+    MissingPrefixParser { found: Token<'static> },
+    UnterminatedBlock { opened: Token<'static>, found: Token<'static> },
+    InvalidInteger { found: Token<'static> },
+    IntegerOutOfRange { found: Token<'static> },
+    FloatOutOfRange { found: Token<'static> },
+    NestedTooDeeply { found: Token<'static>, limit: usize },
+    UnterminatedString { found: Token<'static> },
+    InvalidEscape { found: Token<'static>, span: Span },
+    UnterminatedComment { found: Token<'static> },
+}
+
+impl ParseError {
+    /// The token the parser was looking at when it gave up.
+    pub fn token(&self) -> &Token<'static> {
+        match self {
+            ParseError::UnexpectedToken { found, .. }
+            | ParseError::MissingPrefixParser { found }
+            | ParseError::UnterminatedBlock { found, .. }
+            | ParseError::InvalidInteger { found }
+            | ParseError::IntegerOutOfRange { found }
+            | ParseError::FloatOutOfRange { found }
+            | ParseError::NestedTooDeeply { found, .. }
+            | ParseError::UnterminatedString { found }
+            | ParseError::InvalidEscape { found, .. }
+            | ParseError::UnterminatedComment { found } => found,
+        }
+    }
+
+    /// Whether the error only says that the input stopped early, such as an
+    /// unclosed `{` or a trailing operator, so more input could fix it.
+    pub fn is_incomplete_input(&self) -> bool {
+        match self {
+            ParseError::UnterminatedString { .. } | ParseError::UnterminatedComment { .. } => true,
+            ParseError::UnexpectedToken { found, .. }
+            | ParseError::MissingPrefixParser { found }
+            | ParseError::UnterminatedBlock { found, .. } => found.token_type == Tokens::EOF,
+            _ => false,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::InvalidEscape { span, .. } => *span,
+            _ => self.token().span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken { expected, found } => write!(
+                f,
+                "expected next token to be {:?}, got {:?} instead",
+                expected, found.token_type
+            ),
+            ParseError::MissingPrefixParser { found } => {
+                write!(f, "no prefix parse function for {:?} found", found.token_type)
+            }
+            ParseError::UnterminatedBlock { found, .. } => write!(
+                f,
+                "expected RBRACE to close block, got {:?} instead",
+                found.token_type
+            ),
+            ParseError::InvalidInteger { found } => {
+                write!(f, "could not parse {:?} as integer", found.literal)
+            }
+            ParseError::IntegerOutOfRange { found } => {
+                write!(f, "integer literal {} is out of range", found.literal)
+            }
+            ParseError::FloatOutOfRange { found } => {
+                write!(f, "float literal {} is out of range", found.literal)
+            }
+            ParseError::NestedTooDeeply { limit, .. } => {
+                write!(f, "expression nested too deeply, the limit is {} levels", limit)
+            }
+            ParseError::UnterminatedString { .. } => write!(f, "unterminated string literal"),
+            ParseError::UnterminatedComment { .. } => write!(f, "unterminated block comment"),
+            ParseError::InvalidEscape { found, span } => {
+                let offset = span.start - found.span.start;
+                let escape = &found.literal[offset..offset + span.end - span.start];
+                write!(f, "invalid escape sequence `{}` in string literal", escape)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -1,71 +1,173 @@
 use crate::{
-    ast::ast::{Expression, Identifier, Program, Statement},
+    ast::ast::{BlockStatement, Expression, Identifier, Program, Statement, Statements},
     lexer::lexer::{Lexer, LexerIterItem},
-    token::token::{Token, Tokens},
+    parser::error::ParseError,
+    token::token::{Span, Token, Tokens},
 };
-use std::{cell::Cell, collections::HashMap};
+use std::{borrow::Cow, cell::Cell, collections::HashMap, num::IntErrorKind};
 
-type PrefixParseFn = fn() -> Expression;
+/// How deeply expressions may nest before the parser gives up with
+/// [`ParseError::NestedTooDeeply`] instead of risking a stack overflow.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
 
-type InfixParseFn = fn(Expression) -> Expression;
+type PrefixParseFn<'src> = fn(&mut Parser<'src>) -> Option<Expression>;
 
-pub struct Parser {
-    lexer: Cell<LexerIterItem>,
-    current_token: Option<Token>,
-    peek_token: Option<Token>,
-    errors: Vec<String>,
+type InfixParseFn<'src> = fn(&mut Parser<'src>, Expression) -> Option<Expression>;
 
-    prefix_parser_fns: HashMap<Tokens, PrefixParseFn>,
-    infix_parser_fns: HashMap<Tokens, InfixParseFn>,
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+enum Precedence {
+    Lowest,
+    Or,
+    And,
+    Equals,
+    LessGreater,
+    Sum,
+    Product,
+    Prefix,
+    Call,
+    Index,
 }
 
-impl Parser {
-    pub fn new(lexer: Lexer) -> Self {
+fn precedence_of(token_type: Tokens) -> Precedence {
+    match token_type {
+        Tokens::OR => Precedence::Or,
+        Tokens::AND => Precedence::And,
+        Tokens::EQ | Tokens::NOTEQ => Precedence::Equals,
+        Tokens::LT | Tokens::GT | Tokens::LTEQ | Tokens::GTEQ => Precedence::LessGreater,
+        Tokens::PLUS | Tokens::MINUS => Precedence::Sum,
+        Tokens::SLASH | Tokens::ASTERISK | Tokens::PERCENT => Precedence::Product,
+        Tokens::LPAREN => Precedence::Call,
+        Tokens::LBRACKET => Precedence::Index,
+        _ => Precedence::Lowest,
+    }
+}
+
+pub struct Parser<'src> {
+    lexer: Cell<LexerIterItem<'src>>,
+    current_token: Option<Token<'src>>,
+    peek_token: Option<Token<'src>>,
+    errors: Vec<ParseError>,
+    block_depth: usize,
+    depth: usize,
+    max_depth: usize,
+
+    prefix_parser_fns: HashMap<Tokens, PrefixParseFn<'src>>,
+    infix_parser_fns: HashMap<Tokens, InfixParseFn<'src>>,
+}
+
+impl<'src> Parser<'src> {
+    pub fn new(lexer: Lexer<'src>) -> Self {
         let mut parser = Self {
             lexer: Cell::new(lexer.into_iter()),
             current_token: None,
             peek_token: None,
             errors: vec![],
+            block_depth: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
             infix_parser_fns: HashMap::new(),
             prefix_parser_fns: HashMap::new(),
         };
+
+        parser.register_prefix(Tokens::IDENT, Parser::parse_identifier);
+        parser.register_prefix(Tokens::INT, Parser::parse_integer_literal);
+        parser.register_prefix(Tokens::FLOAT, Parser::parse_float_literal);
+        parser.register_prefix(Tokens::STRING, Parser::parse_string_literal);
+        parser.register_prefix(Tokens::TRUE, Parser::parse_boolean);
+        parser.register_prefix(Tokens::FALSE, Parser::parse_boolean);
+        parser.register_prefix(Tokens::BANG, Parser::parse_prefix_expression);
+        parser.register_prefix(Tokens::MINUS, Parser::parse_prefix_expression);
+        parser.register_prefix(Tokens::LPAREN, Parser::parse_grouped_expression);
+        parser.register_prefix(Tokens::FUNCTION, Parser::parse_function_literal);
+        parser.register_prefix(Tokens::IF, Parser::parse_if_expression);
+        parser.register_prefix(Tokens::LBRACKET, Parser::parse_array_literal);
+        parser.register_prefix(Tokens::LBRACE, Parser::parse_hash_literal);
+
+        for token_type in [
+            Tokens::PLUS,
+            Tokens::MINUS,
+            Tokens::SLASH,
+            Tokens::ASTERISK,
+            Tokens::EQ,
+            Tokens::NOTEQ,
+            Tokens::LT,
+            Tokens::GT,
+            Tokens::LTEQ,
+            Tokens::GTEQ,
+            Tokens::PERCENT,
+            Tokens::AND,
+            Tokens::OR,
+        ] {
+            parser.register_infix(token_type, Parser::parse_infix_expression);
+        }
+        parser.register_infix(Tokens::LPAREN, Parser::parse_call_expression);
+        parser.register_infix(Tokens::LBRACKET, Parser::parse_index_expression);
+
         parser.next_token();
         parser.next_token();
         parser
     }
 
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
     pub fn parse_program(&mut self) -> Program {
         let program = Program::new();
         self.int_parse_program(program)
     }
 
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
     fn next_token(&mut self) {
-        self.current_token = self.peek_token.clone();
-        self.peek_token = self.lexer.get_mut().next();
+        self.current_token = self.peek_token.take();
+        // Once the lexer is exhausted keep handing out its EOF token, so that
+        // errors at the end of the input still point at a real position.
+        self.peek_token = match self.next_significant_token() {
+            Some(token) => Some(token),
+            None => self.current_token.clone(),
+        };
+    }
+
+    /// Pulls the next token that matters to the grammar from the lexer,
+    /// passing over comments and reporting unterminated ones.
+    fn next_significant_token(&mut self) -> Option<Token<'src>> {
+        loop {
+            let token = self.lexer.get_mut().next()?;
+            match token.token_type {
+                Tokens::COMMENT => {}
+                Tokens::UNTERMINATEDCOMMENT => {
+                    self.errors.push(ParseError::UnterminatedComment { found: token.into_owned() })
+                }
+                _ => return Some(token),
+            }
+        }
     }
 
     fn int_parse_program(&mut self, mut program: Program) -> Program {
-        match self.current_token.clone() {
-            Some(token) if token.token_type != Tokens::EOF => match self.parse_statement() {
+        while let Some(token) = self.current_token.clone() {
+            if token.token_type == Tokens::EOF {
+                break;
+            }
+            match self.parse_statement() {
                 Some(stmt) => {
                     program.push(stmt);
                     self.next_token();
-                    self.int_parse_program(program)
-                }
-                _ => {
-                    self.next_token();
-                    self.int_parse_program(program)
                 }
-            },
-            _ => program,
+                None => self.synchronize(&token),
+            }
         }
+        program
     }
 
     fn parse_statement(&mut self) -> Option<Statement> {
         match self.current_token.as_ref() {
             Some(token) if token.token_type == Tokens::LET => self.parse_let(),
             Some(token) if token.token_type == Tokens::RETURN => self.parse_return(),
-            Some(token) => self.parse_expression(),
+            Some(_) => self.parse_expression_statement(),
             _ => None,
         }
     }
@@ -75,42 +177,459 @@ impl Parser {
             return None;
         }
 
-        let identifier = Identifier {
-            token: self.current_token.clone().unwrap(),
-            value: self.current_token.clone().unwrap().literal,
-        };
+        let token = self.owned_current_token().unwrap();
+        let identifier = Identifier { value: token.literal.to_string(), token };
         if !self.expected_token(Tokens::ASSIGN) {
             return None;
         }
-        while !(self.current_token.clone().unwrap().token_type != Tokens::SEMICOLON) {
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(Tokens::SEMICOLON) {
             self.next_token();
         }
-        // TODO: We are ignoring the following expressions;
-        Some(Statement::LetStatement(identifier.clone(), Expression::Identifier(identifier)))
+        Some(Statement::LetStatement(identifier, value))
     }
 
     fn parse_return(&mut self) -> Option<Statement> {
+        let token = self.owned_current_token().unwrap();
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(Tokens::SEMICOLON) {
+            self.next_token();
+        }
+        Some(Statement::ReturnStatement(token, value))
+    }
+
+    fn parse_expression_statement(&mut self) -> Option<Statement> {
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(Tokens::SEMICOLON) {
+            self.next_token();
+        }
+        Some(Statement::ExpressionStatement(expression))
+    }
+
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        let depth = self.depth;
+        let expression = self.parse_nested_expression(precedence);
+        self.depth = depth;
+        expression
+    }
+
+    /// Every call and every operator folded into `left` deepens the tree by
+    /// one level; `depth` counts both, because evaluating, compiling,
+    /// printing and dropping the tree all recurse over it, so a long flat
+    /// chain like `1 + 1 + ...` is as dangerous as deep parentheses.
+    fn parse_nested_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        if !self.enter_nesting() {
+            return None;
+        }
+        let token_type = self.current_token.as_ref()?.token_type;
+        let prefix = match self.prefix_parser_fns.get(&token_type) {
+            Some(prefix) => *prefix,
+            None => {
+                self.no_prefix_parse_fn_error();
+                return None;
+            }
+        };
+        let mut left = prefix(self)?;
+
+        while !self.peek_token_is(Tokens::SEMICOLON) && precedence < self.peek_precedence() {
+            let infix = match self.peek_token.as_ref().and_then(|token| self.infix_parser_fns.get(&token.token_type)) {
+                Some(infix) => *infix,
+                None => return Some(left),
+            };
+            self.next_token();
+            if !self.enter_nesting() {
+                return None;
+            }
+            left = infix(self, left)?;
+        }
+        Some(left)
+    }
+
+    fn enter_nesting(&mut self) -> bool {
+        if self.depth >= self.max_depth {
+            let found = self.owned_current_token().unwrap_or_else(Parser::eof_token);
+            self.errors.push(ParseError::NestedTooDeeply { found, limit: self.max_depth });
+            return false;
+        }
+        self.depth += 1;
+        true
+    }
+
+    fn parse_identifier(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        Some(Expression::Identifier(Identifier {
+            value: token.literal.to_string(),
+            token,
+        }))
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        match integer_magnitude(&token.literal).map(i64::try_from) {
+            Ok(Ok(value)) => Some(Expression::IntegerLiteral(token, value)),
+            Ok(Err(_)) | Err(IntErrorKind::PosOverflow) => {
+                self.errors.push(ParseError::IntegerOutOfRange { found: token });
+                None
+            }
+            Err(_) => {
+                self.errors.push(ParseError::InvalidInteger { found: token });
+                None
+            }
+        }
+    }
+
+    fn parse_float_literal(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        // The lexer only produces well-formed literals, so parsing can only
+        // fail by overflowing to infinity.
+        match token.literal.replace('_', "").parse::<f64>() {
+            Ok(value) if value.is_finite() => Some(Expression::FloatLiteral(token, value)),
+            _ => {
+                self.errors.push(ParseError::FloatOutOfRange { found: token });
+                None
+            }
+        }
+    }
+
+    fn parse_string_literal(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        let value = self.unescape(&token)?;
+        Some(Expression::StringLiteral(token, value))
+    }
+
+    /// Decodes the text of a STRING token, whose literal still carries its
+    /// quotes and escape sequences exactly as they appear in the source.
+    fn unescape(&mut self, token: &Token<'static>) -> Option<String> {
+        let literal = token.literal.as_ref();
+        let mut value = String::new();
+        let mut chars = literal.char_indices().skip(1).peekable();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => return Some(value),
+                '\\' => {
+                    let escaped = match chars.next() {
+                        Some((_, 'n')) => Some('\n'),
+                        Some((_, 't')) => Some('\t'),
+                        Some((_, '"')) => Some('"'),
+                        Some((_, '\\')) => Some('\\'),
+                        Some((_, 'u')) if chars.next_if(|(_, c)| *c == '{').is_some() => {
+                            let mut digits = String::new();
+                            while let Some((_, digit)) = chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+                                digits.push(digit);
+                            }
+                            let closed = chars.next_if(|(_, c)| *c == '}').is_some();
+                            u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .filter(|_| closed && digits.len() <= 6)
+                                .and_then(char::from_u32)
+                        }
+                        _ => None,
+                    };
+                    match escaped {
+                        Some(escaped) => value.push(escaped),
+                        None => {
+                            let end = chars.peek().map_or(literal.len(), |(end, _)| *end);
+                            let span = escape_span(token, offset, end);
+                            self.errors.push(ParseError::InvalidEscape { found: token.clone(), span });
+                            return None;
+                        }
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+        self.errors.push(ParseError::UnterminatedString { found: token.clone() });
+        None
+    }
+
+    fn parse_boolean(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        let value = token.token_type == Tokens::TRUE;
+        Some(Expression::Boolean(token, value))
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<Expression> {
+        let operator = self.owned_current_token()?;
+        self.next_token();
+        if operator.token_type == Tokens::MINUS {
+            if let Some(minimum) = self.parse_minimum_integer(&operator) {
+                return Some(minimum);
+            }
+        }
+        let right = self.parse_expression(Precedence::Prefix)?;
+        Some(Expression::PrefixExpression(operator, Box::new(right)))
+    }
+
+    /// `-9223372036854775808` is `i64::MIN`, although its digits alone are
+    /// out of range. The literal is only folded into the minus when nothing
+    /// binds to it more tightly, as in `-9223372036854775808[0]`.
+    fn parse_minimum_integer(&mut self, minus: &Token<'static>) -> Option<Expression> {
+        let literal = self.current_token.as_ref().filter(|token| token.token_type == Tokens::INT)?;
+        if integer_magnitude(&literal.literal) != Ok(i64::MIN.unsigned_abs()) || self.peek_precedence() > Precedence::Prefix {
+            return None;
+        }
+        let token = Token {
+            token_type: Tokens::INT,
+            literal: format!("-{}", literal.literal).into(),
+            span: Span::new(minus.span.start, literal.span.end, minus.span.line, minus.span.column),
+        };
+        Some(Expression::IntegerLiteral(token, i64::MIN))
+    }
+
+    fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+        let operator = self.owned_current_token()?;
+        let precedence = self.current_precedence();
+        self.next_token();
+        let right = self.parse_expression(precedence)?;
+        Some(Expression::InfixExpression(Box::new(left), operator, Box::new(right)))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<Expression> {
+        self.next_token();
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        if !self.expected_token(Tokens::RPAREN) {
+            return None;
+        }
+        Some(expression)
+    }
+
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        if !self.expected_token(Tokens::LPAREN) {
+            return None;
+        }
+        let parameters = self.parse_function_parameters()?;
+        if !self.expected_token(Tokens::LBRACE) {
+            return None;
+        }
+        let body = self.parse_block_statement()?;
+        Some(Expression::FunctionLiteral(token, parameters, body))
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Identifier>> {
+        let mut parameters = vec![];
+        if self.peek_token_is(Tokens::RPAREN) {
+            self.next_token();
+            return Some(parameters);
+        }
+
+        loop {
+            if !self.expected_token(Tokens::IDENT) {
+                return None;
+            }
+            let token = self.owned_current_token()?;
+            parameters.push(Identifier {
+                value: token.literal.to_string(),
+                token,
+            });
+            if !self.peek_token_is(Tokens::COMMA) {
+                break;
+            }
+            self.next_token();
+        }
+
+        if !self.expected_token(Tokens::RPAREN) {
+            return None;
+        }
+        Some(parameters)
+    }
+
+    fn parse_if_expression(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        if !self.expected_token(Tokens::LPAREN) {
+            return None;
+        }
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        if !self.expected_token(Tokens::RPAREN) || !self.expected_token(Tokens::LBRACE) {
+            return None;
+        }
+        let consequence = self.parse_block_statement()?;
+
+        let alternative = if self.peek_token_is(Tokens::ELSE) {
+            self.next_token();
+            if !self.expected_token(Tokens::LBRACE) {
+                return None;
+            }
+            Some(self.parse_block_statement()?)
+        } else {
+            None
+        };
+        Some(Expression::IfExpression(token, Box::new(condition), consequence, alternative))
+    }
+
+    fn parse_block_statement(&mut self) -> Option<BlockStatement> {
+        let depth = self.depth;
+        let block = self.parse_nested_block_statement();
+        self.depth = depth;
+        block
+    }
+
+    fn parse_nested_block_statement(&mut self) -> Option<BlockStatement> {
+        if !self.enter_nesting() {
+            return None;
+        }
+        let token = self.owned_current_token()?;
+        let mut statements = Statements::new();
+        self.block_depth += 1;
+        self.next_token();
+        while !self.current_token_is(Tokens::RBRACE) {
+            let start = match self.current_token.clone() {
+                Some(start) if start.token_type != Tokens::EOF => start,
+                found => {
+                    let found = found.map_or_else(Parser::eof_token, Token::into_owned);
+                    self.errors.push(ParseError::UnterminatedBlock { opened: token, found });
+                    self.block_depth -= 1;
+                    return None;
+                }
+            };
+            match self.parse_statement() {
+                Some(statement) => {
+                    statements.push(statement);
+                    self.next_token();
+                }
+                None => self.synchronize(&start),
+            }
+        }
+        self.block_depth -= 1;
+        Some(BlockStatement { token, statements })
+    }
+
+    fn parse_call_expression(&mut self, function: Expression) -> Option<Expression> {
+        let arguments = self.parse_expression_list(Tokens::RPAREN)?;
+        Some(Expression::CallExpression(Box::new(function), arguments, self.current_span()))
+    }
+
+    fn parse_array_literal(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        let elements = self.parse_expression_list(Tokens::RBRACKET)?;
+        Some(Expression::ArrayLiteral(token, elements, self.current_span()))
+    }
+
+    fn parse_index_expression(&mut self, left: Expression) -> Option<Expression> {
+        self.next_token();
+        let index = self.parse_expression(Precedence::Lowest)?;
+        if !self.expected_token(Tokens::RBRACKET) {
+            return None;
+        }
+        Some(Expression::IndexExpression(Box::new(left), Box::new(index), self.current_span()))
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        let token = self.owned_current_token()?;
+        let mut pairs = vec![];
+        while !self.peek_token_is(Tokens::RBRACE) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+            if !self.expected_token(Tokens::COLON) {
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((key, value));
+            if !self.peek_token_is(Tokens::RBRACE) && !self.expected_token(Tokens::COMMA) {
+                return None;
+            }
+        }
+        self.next_token();
+        Some(Expression::HashLiteral(token, pairs, self.current_span()))
+    }
+
+    /// Parses comma separated expressions up to and including `end`.
+    fn parse_expression_list(&mut self, end: Tokens) -> Option<Vec<Expression>> {
+        let mut expressions = vec![];
+        if self.peek_token_is(end) {
+            self.next_token();
+            return Some(expressions);
+        }
+
         self.next_token();
-        while !(self.current_token.clone().unwrap().token_type != Tokens::SEMICOLON) {
+        expressions.push(self.parse_expression(Precedence::Lowest)?);
+        while self.peek_token_is(Tokens::COMMA) {
+            self.next_token();
             self.next_token();
+            expressions.push(self.parse_expression(Precedence::Lowest)?);
         }
-        // TODO: We are ignoring the following expressions;
-        let expression = Expression::Identifier(Identifier {
-            token: self.current_token.clone().unwrap(),
-            value: self.current_token.clone().unwrap().literal,
-        });
-        Some(Statement::ReturnStatement(Token { token_type: Tokens::RETURN, literal: self.current_token.clone().unwrap().literal }, expression))
+
+        if !self.expected_token(end) {
+            return None;
+        }
+        Some(expressions)
     }
 
-    fn parse_expression(&mut self) -> Option<Statement> {
-        //TODO: Redo this
-        let expression = self.parse_prefix(self.current_token.clone().unwrap().token_type);
-        if self.peek_token.clone().unwrap().token_type == Tokens::SEMICOLON {
+    /// Skips the rest of a statement that failed to parse, starting at
+    /// `start`, and leaves the parser on the first token of the next one:
+    /// just past a `;`, on a `let`/`return` keyword, on the `}` closing the
+    /// enclosing block, or on EOF. Only the first error of a statement is
+    /// reported, everything up to the synchronisation point is discarded.
+    fn synchronize(&mut self, start: &Token) {
+        if self.current_token_is(Tokens::SEMICOLON) {
+            self.next_token();
+            return;
+        }
+        let at_start = matches!(&self.current_token, Some(token) if token.span == start.span);
+        let closes_block = self.current_token_is(Tokens::RBRACE) && self.block_depth > 0;
+        // Braces opened inside the skipped tokens are matched up so that the
+        // statement boundaries within them are not mistaken for ours.
+        let mut skipped_braces = 0;
+        if at_start || !(closes_block || self.at_statement_start() || self.current_token_is(Tokens::EOF)) {
+            if self.current_token_is(Tokens::LBRACE) {
+                skipped_braces += 1;
+            }
+            self.next_token();
+        }
+        loop {
+            match self.current_token.as_ref().map(|token| token.token_type) {
+                Some(Tokens::EOF) | None => return,
+                Some(Tokens::LBRACE) => skipped_braces += 1,
+                Some(Tokens::RBRACE) if skipped_braces > 0 => skipped_braces -= 1,
+                _ if skipped_braces > 0 => {}
+                Some(Tokens::SEMICOLON) => {
+                    self.next_token();
+                    return;
+                }
+                Some(Tokens::RBRACE) if self.block_depth > 0 => return,
+                _ if self.at_statement_start() => return,
+                _ => {}
+            }
             self.next_token();
         }
-        Some(Statement::ExpressionStatement(self.current_token.clone().unwrap(), expression))
     }
 
+    fn at_statement_start(&self) -> bool {
+        self.current_token_is(Tokens::LET) || self.current_token_is(Tokens::RETURN)
+    }
+
+    /// The current token, detached from the source so it can live in the AST.
+    fn owned_current_token(&self) -> Option<Token<'static>> {
+        self.current_token.clone().map(Token::into_owned)
+    }
+
+    /// The span of the current token; right after a list, index or hash
+    /// is parsed, that is its closing delimiter.
+    fn current_span(&self) -> Span {
+        self.current_token.as_ref().map_or_else(Span::default, |token| token.span)
+    }
+
+    fn current_token_is(&self, token_type: Tokens) -> bool {
+        matches!(self.current_token.as_ref(), Some(token) if token.token_type == token_type)
+    }
+
+    fn peek_token_is(&self, token_type: Tokens) -> bool {
+        matches!(self.peek_token.as_ref(), Some(token) if token.token_type == token_type)
+    }
+
+    fn current_precedence(&self) -> Precedence {
+        self.current_token.as_ref().map_or(Precedence::Lowest, |token| precedence_of(token.token_type))
+    }
+
+    fn peek_precedence(&self) -> Precedence {
+        self.peek_token.as_ref().map_or(Precedence::Lowest, |token| precedence_of(token.token_type))
+    }
 
     fn expected_token(&mut self, token_type: Tokens) -> bool {
         match self.peek_token.clone() {
@@ -126,35 +645,72 @@ impl Parser {
     }
 
     fn token_errored(&mut self, token_type: Tokens) {
-        self.errors.push(String::from(format!("expected next token to be {:?}, got {:?} instead", token_type, self.current_token.as_ref().unwrap())))
+        let found = self.peek_token.clone().map_or_else(Parser::eof_token, Token::into_owned);
+        self.errors.push(ParseError::UnexpectedToken { expected: token_type, found })
+    }
+
+    fn no_prefix_parse_fn_error(&mut self) {
+        let found = self.owned_current_token().unwrap_or_else(Parser::eof_token);
+        self.errors.push(ParseError::MissingPrefixParser { found })
     }
 
-    fn register_prefix(&mut self, token_type: Tokens, prefix_parser_fn: PrefixParseFn) {
+    fn eof_token() -> Token<'static> {
+        Token { token_type: Tokens::EOF, literal: Cow::Borrowed(""), span: Span::default() }
+    }
+
+    fn register_prefix(&mut self, token_type: Tokens, prefix_parser_fn: PrefixParseFn<'src>) {
         self.prefix_parser_fns.insert(token_type, prefix_parser_fn);
     }
 
-    fn register_infix(&mut self, token_type: Tokens, infix_parser_fn: InfixParseFn) {
+    fn register_infix(&mut self, token_type: Tokens, infix_parser_fn: InfixParseFn<'src>) {
         self.infix_parser_fns.insert(token_type, infix_parser_fn);
     }
 }
+/// The span of `token.literal[start..end]`, which may sit on a later line
+/// than the token itself.
+fn escape_span(token: &Token<'static>, start: usize, end: usize) -> Span {
+    let before = &token.literal[..start];
+    let (line, column) = match before.rfind('\n') {
+        Some(newline) => (
+            token.span.line + before.matches('\n').count(),
+            before[newline + 1..].chars().count() + 1,
+        ),
+        None => (token.span.line, token.span.column + before.chars().count()),
+    };
+    Span::new(token.span.start + start, token.span.start + end, line, column)
+}
+
+/// The value of an INT literal's digits. It can exceed `i64::MAX`, by one
+/// for the magnitude of `i64::MIN`.
+fn integer_magnitude(literal: &str) -> Result<u64, IntErrorKind> {
+    let (radix, digits) = match literal.get(..2) {
+        Some("0x") => (16, &literal[2..]),
+        Some("0o") => (8, &literal[2..]),
+        Some("0b") => (2, &literal[2..]),
+        _ => (10, literal),
+    };
+    u64::from_str_radix(&digits.replace('_', ""), radix).map_err(|error| *error.kind())
+}
+
 #[cfg(test)]
 mod parser_tester {
     use crate::ast::ast::{Identifier, Expression, Statement};
     use crate::ast::ast::Statement::{LetStatement, ReturnStatement, ExpressionStatement};
     use crate::lexer::lexer::Lexer;
-    use crate::token::token::{Tokens, Token};
+    use crate::token::token::{Span, Tokens, Token};
 
-    use super::Parser;
+    use super::{Parser, DEFAULT_MAX_DEPTH};
+    use crate::parser::error::ParseError;
 
     #[test]
-    fn let_parser() -> Result<(), Vec<String>>{
+    fn let_parser() -> Result<(), Vec<ParseError>>{
         let input = "
             let x = 5;
             let y = 10;
             let foobar = 8383883;
         ";
 
-        let lexer = Lexer::new(String::from(input));
+        let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
 
         let program = parser.parse_program();
@@ -164,11 +720,13 @@ mod parser_tester {
         assert_eq!(program.len(), 3);
 
         let mut expected_identifiers = vec!["x", "y", "foobar"];
+        let mut expected_values = vec!["5", "10", "8383883"];
 
         for statement in program.into_iter() {
             match statement {
-                LetStatement(identifier, _) => {
+                LetStatement(identifier, value) => {
                     assert_eq!(identifier.value, expected_identifiers.remove(0));
+                    assert_eq!(value.to_string(), expected_values.remove(0));
                 },
                 _ => panic!("Unexpected statement")
             }
@@ -177,14 +735,14 @@ mod parser_tester {
     }
 
     #[test]
-    fn return_parser() -> Result<(), Vec<String>>{
+    fn return_parser() -> Result<(), Vec<ParseError>>{
         let input = "
             return 5;
             return 10;
             return 1232123;
         ";
 
-        let lexer = Lexer::new(String::from(input));
+        let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
         let program = parser.parse_program();
 
@@ -193,9 +751,14 @@ mod parser_tester {
 
         check_parser_errors(parser)?;
 
+        let mut expected_values = vec!["5", "10", "1232123"];
+
         for statement in program.into_iter() {
             match statement {
-                ReturnStatement(token, _) => assert_eq!(token.token_type, Tokens::RETURN),
+                ReturnStatement(token, value) => {
+                    assert_eq!(token.token_type, Tokens::RETURN);
+                    assert_eq!(value.to_string(), expected_values.remove(0));
+                },
                 _ => panic!("Unexpected statement")
             }
         }
@@ -205,10 +768,33 @@ mod parser_tester {
     }
 
     #[test]
-    fn identifier_expression() -> Result<(), Vec<String>>{
+    fn let_and_return_expressions() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            ("let x = 5 * (2 + y);", "let x = (5 * (2 + y));"),
+            ("let y = true;", "let y = true;"),
+            ("let foobar = add(x, -y);", "let foobar = add(x, (-y));"),
+            ("return x + y * z;", "return (x + (y * z));"),
+            ("return !false;", "return (!false);"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            assert_eq!(program.len(), 1);
+            assert_eq!(program[0].to_string(), expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn identifier_expression() -> Result<(), Vec<ParseError>>{
         let input = "foobar;";
 
-        let lexer = Lexer::new(String::from(input));
+        let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
         let program = parser.parse_program();
         
@@ -216,16 +802,762 @@ mod parser_tester {
 
         assert_eq!(program.len(), 1);
 
-        assert_eq!(vec![Statement::ExpressionStatement(Expression::Identifier(Identifier { token: Token {token_type: Tokens::IDENT, literal: String::from("foobar")}, value: String::from("foobar") }))], program);
+        assert_eq!(vec![Statement::ExpressionStatement(Expression::Identifier(Identifier { token: Token { token_type: Tokens::IDENT, literal: "foobar".into(), span: Span::new(0, 6, 1, 1) }, value: String::from("foobar") }))], program);
+
+        Ok(())
+
+
+    }
+
+
+    #[test]
+    fn integer_literal_expression() -> Result<(), Vec<ParseError>> {
+        let lexer = Lexer::new("5;");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        assert_eq!(vec![ExpressionStatement(Expression::IntegerLiteral(Token { token_type: Tokens::INT, literal: "5".into(), span: Span::new(0, 1, 1, 1) }, 5))], program);
+        Ok(())
+    }
+
+    #[test]
+    fn string_literal_expression() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            (r#""hello world";"#, "hello world"),
+            (r#""";"#, ""),
+            (r#""a\tb\nc";"#, "a\tb\nc"),
+            (r#""say \"hi\" \\o/";"#, "say \"hi\" \\o/"),
+            (r#""\u{48}\u{e9}\u{1F980}";"#, "Hé🦀"),
+            ("\"two\nlines\";", "two\nlines"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            match &program[..] {
+                [ExpressionStatement(Expression::StringLiteral(token, value))] => {
+                    assert_eq!(value, expected, "{}", input);
+                    assert_eq!(token.literal, input.trim_end_matches(';'));
+                }
+                _ => panic!("expected a single string literal for {}, got {:?}", input, program),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn string_literal_errors() {
+        let tests = vec![
+            ("let s = \"abc", "unterminated string literal", Span::new(8, 12, 1, 9)),
+            ("\"abc\\\"", "unterminated string literal", Span::new(0, 6, 1, 1)),
+            ("\"a\\qb\";", "invalid escape sequence `\\q` in string literal", Span::new(2, 4, 1, 3)),
+            ("\"x\ny \\u{110000}\";", "invalid escape sequence `\\u{110000}` in string literal", Span::new(5, 15, 2, 3)),
+            ("\"\\u{zz}\";", "invalid escape sequence `\\u{` in string literal", Span::new(1, 4, 1, 2)),
+            ("\"\\u{1234567}\";", "invalid escape sequence `\\u{1234567}` in string literal", Span::new(1, 12, 1, 2)),
+            ("\"\\", "invalid escape sequence `\\` in string literal", Span::new(1, 2, 1, 2)),
+        ];
+
+        for (input, message, span) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            assert_eq!(parser.errors().len(), 1, "{:?}: {:?}", input, parser.errors());
+            assert_eq!(parser.errors()[0].to_string(), message, "{:?}", input);
+            assert_eq!(parser.errors()[0].span(), span, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn integer_literal_bases() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            ("1_000_000;", 1_000_000),
+            ("1__2_;", 12),
+            ("0xff;", 255),
+            ("0xDEAD_beef;", 0xdead_beef),
+            ("0o755;", 0o755),
+            ("0b1010_1010;", 0b1010_1010),
+            ("0x_1;", 1),
+            ("9223372036854775807;", i64::MAX),
+            ("0x7fffffffffffffff;", i64::MAX),
+            ("-9223372036854775808;", i64::MIN),
+            ("-0x8000_0000_0000_0000;", i64::MIN),
+            ("007;", 7),
+        ];
 
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            match &program[..] {
+                [ExpressionStatement(Expression::IntegerLiteral(token, value))] => {
+                    assert_eq!(*value, expected, "{}", input);
+                    assert_eq!(token.literal, input.trim_end_matches(';'));
+                }
+                _ => panic!("expected a single integer literal for {}, got {:?}", input, program),
+            }
+        }
         Ok(())
+    }
+
+    #[test]
+    fn integer_literal_errors() {
+        let tests = vec![
+            ("99999999999999999999;", "integer literal 99999999999999999999 is out of range", Span::new(0, 20, 1, 1)),
+            ("let x = 1 + 9223372036854775808;", "integer literal 9223372036854775808 is out of range", Span::new(12, 31, 1, 13)),
+            ("0x1_0000_0000_0000_0000;", "integer literal 0x1_0000_0000_0000_0000 is out of range", Span::new(0, 23, 1, 1)),
+            ("-9223372036854775809;", "integer literal 9223372036854775809 is out of range", Span::new(1, 20, 1, 2)),
+            ("-9223372036854775808[0];", "integer literal 9223372036854775808 is out of range", Span::new(1, 20, 1, 2)),
+            ("0b102;", "could not parse \"0b102\" as integer", Span::new(0, 5, 1, 1)),
+            ("0o8;", "could not parse \"0o8\" as integer", Span::new(0, 3, 1, 1)),
+            ("0xfg;", "could not parse \"0xfg\" as integer", Span::new(0, 4, 1, 1)),
+            ("0x;", "could not parse \"0x\" as integer", Span::new(0, 2, 1, 1)),
+            ("0b_;", "could not parse \"0b_\" as integer", Span::new(0, 3, 1, 1)),
+        ];
 
+        for (input, message, span) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
 
+            assert_eq!(parser.errors().len(), 1, "{:?}: {:?}", input, parser.errors());
+            assert_eq!(parser.errors()[0].to_string(), message, "{:?}", input);
+            assert_eq!(parser.errors()[0].span(), span, "{:?}", input);
+        }
     }
 
+    #[test]
+    fn float_literal_expression() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            ("1.5;", 1.5),
+            ("0.25e3;", 250.0),
+            ("1e-3;", 0.001),
+            ("1_000.5;", 1000.5),
+            ("1.7976931348623157e308;", f64::MAX),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            match &program[..] {
+                [ExpressionStatement(Expression::FloatLiteral(token, value))] => {
+                    assert_eq!(*value, expected, "{}", input);
+                    assert_eq!(token.literal, input.trim_end_matches(';'));
+                }
+                _ => panic!("expected a single float literal for {}, got {:?}", input, program),
+            }
+        }
+
+        let lexer = Lexer::new("let x = 1e400;");
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+        assert_eq!(
+            parser.errors(),
+            &[ParseError::FloatOutOfRange { found: Token { token_type: Tokens::FLOAT, literal: "1e400".into(), span: Span::new(8, 13, 1, 9) } }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn boolean_expression() -> Result<(), Vec<ParseError>> {
+        let lexer = Lexer::new("true; false;");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        assert_eq!(
+            vec![
+                ExpressionStatement(Expression::Boolean(Token { token_type: Tokens::TRUE, literal: "true".into(), span: Span::new(0, 4, 1, 1) }, true)),
+                ExpressionStatement(Expression::Boolean(Token { token_type: Tokens::FALSE, literal: "false".into(), span: Span::new(6, 11, 1, 7) }, false)),
+            ],
+            program
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prefix_expressions() -> Result<(), Vec<ParseError>> {
+        let tests = vec![("!5;", "!", "5"), ("-15;", "-", "15"), ("!true;", "!", "true"), ("!false;", "!", "false")];
+
+        for (input, expected_operator, expected_right) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            assert_eq!(program.len(), 1);
+            match &program[0] {
+                ExpressionStatement(Expression::PrefixExpression(operator, right)) => {
+                    assert_eq!(operator.literal, expected_operator);
+                    assert_eq!(right.to_string(), expected_right);
+                }
+                statement => panic!("Unexpected statement {:?}", statement),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn infix_expressions() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            ("5 + 5;", "5", "+", "5"),
+            ("5 - 5;", "5", "-", "5"),
+            ("5 * 5;", "5", "*", "5"),
+            ("5 / 5;", "5", "/", "5"),
+            ("5 > 5;", "5", ">", "5"),
+            ("5 < 5;", "5", "<", "5"),
+            ("5 == 5;", "5", "==", "5"),
+            ("5 != 5;", "5", "!=", "5"),
+            ("true == true;", "true", "==", "true"),
+            ("true != false;", "true", "!=", "false"),
+        ];
+
+        for (input, expected_left, expected_operator, expected_right) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            assert_eq!(program.len(), 1);
+            match &program[0] {
+                ExpressionStatement(Expression::InfixExpression(left, operator, right)) => {
+                    assert_eq!(left.to_string(), expected_left);
+                    assert_eq!(operator.literal, expected_operator);
+                    assert_eq!(right.to_string(), expected_right);
+                }
+                statement => panic!("Unexpected statement {:?};", statement),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn operator_precedence() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            ("-a * b;", "((-a) * b)"),
+            ("!-a;", "(!(-a))"),
+            ("a + b + c;", "((a + b) + c)"),
+            ("a + b - c;", "((a + b) - c)"),
+            ("a * b * c;", "((a * b) * c)"),
+            ("a * b / c;", "((a * b) / c)"),
+            ("a + b / c;", "(a + (b / c))"),
+            ("a + b * c + d / e - f;", "(((a + (b * c)) + (d / e)) - f)"),
+            ("3 + 4; -5 * 5;", "(3 + 4)((-5) * 5)"),
+            ("5 > 4 == 3 < 4;", "((5 > 4) == (3 < 4))"),
+            ("5 < 4 != 3 > 4;", "((5 < 4) != (3 > 4))"),
+            ("3 + 4 * 5 == 3 * 1 + 4 * 5;", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))"),
+            ("true;", "true"),
+            ("3 > 5 == false;", "((3 > 5) == false)"),
+            ("3 < 5 == true;", "((3 < 5) == true)"),
+            ("1 + (2 + 3) + 4;", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2;", "((5 + 5) * 2)"),
+            ("2 / (5 + 5);", "(2 / (5 + 5))"),
+            ("-(5 + 5);", "(-(5 + 5))"),
+            ("!(true == true);", "(!(true == true))"),
+            ("a * [1, 2, 3, 4][b * c] * d;", "((a * ([1, 2, 3, 4][(b * c)])) * d)"),
+            ("add(a * b[2], b[1], 2 * [1, 2][1]);", "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))"),
+            ("-a[0];", "(-(a[0]))"),
+            ("f(x)[0][1];", "((f(x)[0])[1])"),
+            ("-1.5 * 2 + x / 0.5e1;", "(((-1.5) * 2) + (x / 0.5e1))"),
+            ("a % b * c;", "((a % b) * c)"),
+            ("a + b % c;", "(a + (b % c))"),
+            ("a <= b == c >= d;", "((a <= b) == (c >= d))"),
+            ("a < b && c > d;", "((a < b) && (c > d))"),
+            ("a || b && c;", "(a || (b && c))"),
+            ("a && b || c && d;", "((a && b) || (c && d))"),
+            ("a == b || !c;", "((a == b) || (!c))"),
+            ("a || b || c;", "((a || b) || c)"),
+            ("a + add(b * c) + d;", "((a + add((b * c))) + d)"),
+            ("add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8));", "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))"),
+            ("add(a + b + c * d / f + g);", "add((((a + b) + ((c * d) / f)) + g))"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            let actual: String = program.iter().map(|statement| statement.to_string()).collect();
+            assert_eq!(actual, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn if_expression() -> Result<(), Vec<ParseError>> {
+        let lexer = Lexer::new("if (x < y) { x }");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        assert_eq!(program.len(), 1);
+        match &program[0] {
+            ExpressionStatement(Expression::IfExpression(_, condition, consequence, alternative)) => {
+                assert_eq!(condition.to_string(), "(x < y)");
+                assert_eq!(consequence.statements.len(), 1);
+                assert_eq!(consequence.statements[0].to_string(), "x");
+                assert_eq!(alternative, &None);
+            }
+            statement => panic!("Unexpected statement {:?}", statement),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn if_else_expression() -> Result<(), Vec<ParseError>> {
+        let lexer = Lexer::new("if (x < y) { x } else { let z = y; z }");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        assert_eq!(program.len(), 1);
+        assert_eq!(program[0].to_string(), "if (x < y) { x } else { let z = y; z }");
+        match &program[0] {
+            ExpressionStatement(Expression::IfExpression(_, _, _, Some(alternative))) => {
+                assert_eq!(alternative.statements.len(), 2);
+            }
+            statement => panic!("Unexpected statement {:?}", statement),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unterminated_block() {
+        let lexer = Lexer::new("if (x) { x;");
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        assert_eq!(
+            parser.errors(),
+            &[ParseError::UnterminatedBlock {
+                opened: Token { token_type: Tokens::LBRACE, literal: "{".into(), span: Span::new(7, 8, 1, 8) },
+                found: Token { token_type: Tokens::EOF, literal: "".into(), span: Span::new(11, 11, 1, 12) },
+            }]
+        );
+        assert_eq!(parser.errors()[0].to_string(), "expected RBRACE to close block, got EOF instead");
+    }
+
+    #[test]
+    fn function_literal() -> Result<(), Vec<ParseError>> {
+        let lexer = Lexer::new("fn(x, y) { x + y; };");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        assert_eq!(program.len(), 1);
+        match &program[0] {
+            ExpressionStatement(Expression::FunctionLiteral(_, parameters, body)) => {
+                let parameters: Vec<&str> = parameters.iter().map(|parameter| parameter.value.as_str()).collect();
+                assert_eq!(parameters, vec!["x", "y"]);
+                assert_eq!(body.statements.len(), 1);
+                assert_eq!(body.statements[0].to_string(), "(x + y)");
+            }
+            statement => panic!("Unexpected statement {:?}", statement),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn function_parameters() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            ("fn() {};", vec![]),
+            ("fn(x) {};", vec!["x"]),
+            ("fn(x, y, z) {};", vec!["x", "y", "z"]),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            match &program[0] {
+                ExpressionStatement(Expression::FunctionLiteral(_, parameters, _)) => {
+                    let parameters: Vec<&str> = parameters.iter().map(|parameter| parameter.value.as_str()).collect();
+                    assert_eq!(parameters, expected);
+                }
+                statement => panic!("Unexpected statement {:?}", statement),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn call_expression() -> Result<(), Vec<ParseError>> {
+        let lexer = Lexer::new("add(1, 2 * 3, 4 + 5);");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        assert_eq!(program.len(), 1);
+        match &program[0] {
+            ExpressionStatement(Expression::CallExpression(function, arguments, _)) => {
+                assert_eq!(function.to_string(), "add");
+                let arguments: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+                assert_eq!(arguments, vec!["1", "(2 * 3)", "(4 + 5)"]);
+            }
+            statement => panic!("Unexpected statement {:?}", statement),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn array_literal() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            ("[1, 2 * 2, 3 + 3];", vec!["1", "(2 * 2)", "(3 + 3)"]),
+            ("[];", vec![]),
+            ("[fn(x) { x }, \"two\", [3]];", vec!["fn(x) { x }", "\"two\"", "[3]"]),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            match &program[..] {
+                [ExpressionStatement(Expression::ArrayLiteral(_, elements, _))] => {
+                    let elements: Vec<String> = elements.iter().map(|element| element.to_string()).collect();
+                    assert_eq!(elements, expected, "{}", input);
+                }
+                _ => panic!("expected a single array literal for {}, got {:?}", input, program),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn index_expression() -> Result<(), Vec<ParseError>> {
+        let lexer = Lexer::new("myArray[1 + 1]");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        match &program[..] {
+            [ExpressionStatement(Expression::IndexExpression(left, index, _))] => {
+                assert_eq!(left.to_string(), "myArray");
+                assert_eq!(index.to_string(), "(1 + 1)");
+            }
+            _ => panic!("expected a single index expression, got {:?}", program),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hash_literal() -> Result<(), Vec<ParseError>> {
+        let tests = vec![
+            (r#"{"one": 1, "two": 2, "three": 3}"#, vec![("\"one\"", "1"), ("\"two\"", "2"), ("\"three\"", "3")]),
+            ("{}", vec![]),
+            ("{true: 1, 2: \"b\",}", vec![("true", "1"), ("2", "\"b\"")]),
+            (r#"{"one": 0 + 1, "two": 10 - 8}"#, vec![("\"one\"", "(0 + 1)"), ("\"two\"", "(10 - 8)")]),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            let program = parser.parse_program();
+
+            check_parser_errors(parser)?;
+
+            match &program[..] {
+                [ExpressionStatement(Expression::HashLiteral(_, pairs, _))] => {
+                    let pairs: Vec<(String, String)> =
+                        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+                    let expected: Vec<(String, String)> =
+                        expected.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+                    assert_eq!(pairs, expected, "{}", input);
+                }
+                _ => panic!("expected a single hash literal for {}, got {:?}", input, program),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn collection_errors() {
+        let tests = vec![
+            ("[1, 2;", "expected next token to be RBRACKET, got SEMICOLON instead"),
+            ("a[1;", "expected next token to be RBRACKET, got SEMICOLON instead"),
+            (r#"{"a" 1}"#, "expected next token to be COLON, got INT instead"),
+            (r#"{"a": 1 "b": 2}"#, "expected next token to be COMMA, got STRING instead"),
+            (r#"{"a": 1"#, "expected next token to be COMMA, got EOF instead"),
+        ];
+
+        for (input, expected) in tests {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            let errors: Vec<String> = parser.errors().iter().map(|error| error.to_string()).collect();
+            assert_eq!(errors, vec![expected], "{}", input);
+        }
+    }
+
+    #[test]
+    fn ignores_comments() -> Result<(), Vec<ParseError>> {
+        let input = "// add them up\nlet x = 1 /* one */ + /* two */ 2; // done";
+        let lexer = Lexer::new(input).with_trivia();
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        let actual: String = program.iter().map(|statement| statement.to_string()).collect();
+        assert_eq!(actual, "let x = (1 + 2);");
+        Ok(())
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let lexer = Lexer::new("let x = 1;\n/* never closed");
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        assert_eq!(program.len(), 1);
+        assert_eq!(parser.errors().len(), 1);
+        assert_eq!(parser.errors()[0].to_string(), "unterminated block comment");
+        assert_eq!(parser.errors()[0].span(), Span::new(11, 26, 2, 1));
+    }
+
+    #[test]
+    fn missing_prefix_parser() {
+        let lexer = Lexer::new("+5;");
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        assert_eq!(
+            parser.errors(),
+            &[ParseError::MissingPrefixParser { found: Token { token_type: Tokens::PLUS, literal: "+".into(), span: Span::new(0, 1, 1, 1) } }]
+        );
+        assert_eq!(parser.errors()[0].to_string(), "no prefix parse function for PLUS found");
+    }
+
+    #[test]
+    fn unexpected_token() {
+        let lexer = Lexer::new("let = 5; let x 5;");
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        let errors: Vec<String> = parser.errors().iter().map(|error| error.to_string()).collect();
+        assert_eq!(errors[0], "expected next token to be IDENT, got ASSIGN instead");
+        assert!(errors.contains(&String::from("expected next token to be ASSIGN, got INT instead")));
+        assert_eq!(
+            parser.errors()[0].token(),
+            &Token { token_type: Tokens::ASSIGN, literal: "=".into(), span: Span::new(4, 5, 1, 5) }
+        );
+    }
+
+
+    #[test]
+    fn parse_error_spans() {
+        let lexer = Lexer::new("let x = 1;\nlet y 2;");
+        let mut parser = Parser::new(lexer);
+        parser.parse_program();
+
+        assert_eq!(parser.errors()[0].span(), Span::new(17, 18, 2, 7));
+    }
+
+    #[test]
+    fn expression_spans_reach_closing_delimiters() {
+        let tests = vec![
+            ("add(1, 2)", Span::new(0, 9, 1, 1)),
+            ("f()", Span::new(0, 3, 1, 1)),
+            ("[1, 2 * 3]", Span::new(0, 10, 1, 1)),
+            ("[]", Span::new(0, 2, 1, 1)),
+            ("xs[1 + 1]", Span::new(0, 9, 1, 1)),
+            ("{1: 2, 3: f(4) }", Span::new(0, 16, 1, 1)),
+            ("{}", Span::new(0, 2, 1, 1)),
+            ("f(x)[0](\n)", Span::new(0, 10, 1, 1)),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(Lexer::new(input));
+            let program = parser.parse_program();
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            match &program[..] {
+                [ExpressionStatement(expression)] => assert_eq!(expression.span(), expected, "{}", input),
+                program => panic!("Unexpected program {:?}", program),
+            }
+        }
+    }
+
+    #[test]
+    fn reports_every_independent_error() {
+        let input = "
+            let x 5;
+            let = 10;
+            let ok = 1;
+            let y = 5 +
+            let z = * 2;
+            return ok;
+        ";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        let errors: Vec<(String, usize)> = parser.errors().iter().map(|error| (error.to_string(), error.span().line)).collect();
+        assert_eq!(
+            errors,
+            vec![
+                (String::from("expected next token to be ASSIGN, got INT instead"), 2),
+                (String::from("expected next token to be IDENT, got ASSIGN instead"), 3),
+                (String::from("no prefix parse function for LET found"), 6),
+                (String::from("no prefix parse function for ASTERISK found"), 6),
+            ]
+        );
+        let statements: Vec<String> = program.iter().map(|statement| statement.to_string()).collect();
+        assert_eq!(statements, vec!["let ok = 1;", "return ok;"]);
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let input = "
+            let f = fn(x) {
+                let y = ;
+                let z = x + 1;
+                z
+            };
+            let g = fn() { if (true) { let = 1 } else { 2 } };
+            f(1);
+        ";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        let errors: Vec<String> = parser.errors().iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                String::from("no prefix parse function for SEMICOLON found"),
+                String::from("expected next token to be IDENT, got ASSIGN instead"),
+            ]
+        );
+        let statements: Vec<String> = program.iter().map(|statement| statement.to_string()).collect();
+        assert_eq!(
+            statements,
+            vec![
+                "let f = fn(x) { let z = (x + 1); z };",
+                "let g = fn() { if true {  } else { 2 } };",
+                "f(1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_cascade_errors() {
+        let input = "let a = 1 + @; let b = (2; let c = 3; }";
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        let errors: Vec<String> = parser.errors().iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                String::from("no prefix parse function for ILLEGAL found"),
+                String::from("expected next token to be RPAREN, got SEMICOLON instead"),
+                String::from("no prefix parse function for RBRACE found"),
+            ]
+        );
+        let statements: Vec<String> = program.iter().map(|statement| statement.to_string()).collect();
+        assert_eq!(statements, vec!["let c = 3;"]);
+    }
+
+    #[test]
+    fn parses_many_statements_iteratively() -> Result<(), Vec<ParseError>> {
+        let input = "let x = 1;\n".repeat(100_000);
+        let lexer = Lexer::new(&input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+
+        assert_eq!(program.len(), 100_000);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_deeply_nested_expressions() {
+        let tests = vec![
+            format!("{}1{};", "-(".repeat(10_000), ")".repeat(10_000)),
+            format!("{}1;", "1 + ".repeat(200_000)),
+            format!("x{};", "(1)".repeat(200_000)),
+            format!("{}x{};", "f(".repeat(10_000), ")".repeat(10_000)),
+            format!("{}1{};", "if (true) { ".repeat(10_000), " }".repeat(10_000)),
+        ];
+
+        for input in tests {
+            let lexer = Lexer::new(&input);
+            let mut parser = Parser::new(lexer);
+            parser.parse_program();
+
+            let errors: Vec<String> = parser.errors().iter().map(|error| error.to_string()).collect();
+            assert_eq!(errors, vec![format!("expression nested too deeply, the limit is {} levels", DEFAULT_MAX_DEPTH)]);
+        }
+    }
+
+    #[test]
+    fn parses_flat_chains_within_the_limit() -> Result<(), Vec<ParseError>> {
+        let input = format!("{}1; f{};", "1 + ".repeat(200), "(1)".repeat(200));
+        let lexer = Lexer::new(&input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+        assert_eq!(program.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn configurable_max_depth() -> Result<(), Vec<ParseError>> {
+        let lexer = Lexer::new("(((1)));");
+        let mut parser = Parser::new(lexer).with_max_depth(3);
+        parser.parse_program();
+
+        assert_eq!(
+            parser.errors(),
+            &[ParseError::NestedTooDeeply {
+                found: Token { token_type: Tokens::INT, literal: "1".into(), span: Span::new(3, 4, 1, 4) },
+                limit: 3,
+            }]
+        );
+
+        let lexer = Lexer::new("((1)); 1 + 2; 3;");
+        let mut parser = Parser::new(lexer).with_max_depth(3);
+        let program = parser.parse_program();
+
+        check_parser_errors(parser)?;
+        assert_eq!(program.len(), 3);
+        Ok(())
+    }
 
-    fn check_parser_errors(parser: Parser) -> Result<(), Vec<String>> {
-        if parser.errors.len() > 0 {
+    fn check_parser_errors(parser: Parser) -> Result<(), Vec<ParseError>> {
+        if !parser.errors().is_empty() {
             return Err(parser.errors);
         }
         Ok(())
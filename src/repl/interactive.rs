@@ -0,0 +1,150 @@
+use std::{env, io, path::PathBuf};
+
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter, history::DefaultHistory,
+    validate::Validator, Context, Editor, Helper,
+};
+
+use super::repl::{Session, PROMPT};
+use crate::object::object::Builtin;
+
+const KEYWORDS: &[&str] = &["else", "false", "fn", "if", "let", "return", "true"];
+const COMMANDS: &[&str] = &[":ast", ":env", ":help", ":load", ":reset", ":time", ":tokens"];
+
+/// Runs the REPL on a terminal with line editing, history kept in
+/// `~/.monkey_history` and tab completion. Ctrl-C drops the input being
+/// typed and Ctrl-D ends the session.
+pub fn interactive() -> rustyline::Result<()> {
+    let mut editor: Editor<MonkeyHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(MonkeyHelper::default()));
+    let history = history_path();
+    if let Some(path) = &history {
+        // A missing history file just means this is the first session.
+        let _ = editor.load_history(path);
+    }
+
+    let mut session = Session::new();
+    let mut stdout = io::stdout();
+    let mut prompt = PROMPT;
+    // An editor error ends the session like Ctrl-D, but is reported once the
+    // history is saved.
+    let result = loop {
+        match editor.readline(prompt) {
+            Ok(line) => {
+                // History is a convenience, so failing to keep it must not
+                // end the session.
+                if !line.trim().is_empty() {
+                    if let Err(error) = editor.add_history_entry(line.as_str()) {
+                        eprintln!("could not add to history: {}", error);
+                    }
+                }
+                prompt = session.feed(&line, &mut stdout);
+                if let Some(helper) = editor.helper_mut() {
+                    helper.names = session.names();
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                session.cancel();
+                prompt = PROMPT;
+            }
+            Err(ReadlineError::Eof) => {
+                session.finish(&mut stdout);
+                break Ok(());
+            }
+            Err(error) => break Err(error),
+        }
+    };
+
+    if let Some(path) = &history {
+        if let Err(error) = editor.save_history(path) {
+            eprintln!("could not save history to {}: {}", path.display(), error);
+        }
+    }
+    result
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".monkey_history"))
+}
+
+/// Completes keywords, builtins and the names bound in the session, or commands at
+/// the start of a line.
+#[derive(Default)]
+struct MonkeyHelper {
+    names: Vec<String>,
+}
+
+impl Completer for MonkeyHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete_word(line, pos, &self.names))
+    }
+}
+
+impl Hinter for MonkeyHelper {
+    type Hint = String;
+}
+
+impl Highlighter for MonkeyHelper {}
+
+impl Validator for MonkeyHelper {}
+
+impl Helper for MonkeyHelper {}
+
+/// Finds the identifier ending at `pos` and the words it could complete
+/// to, returning where the identifier starts and the candidates in order.
+fn complete_word(line: &str, pos: usize, names: &[String]) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| unicode_ident::is_xid_continue(*c))
+        .last()
+        .map_or(pos, |(index, _)| index);
+    let word = &line[start..pos];
+
+    if start == 1 && before.starts_with(':') {
+        let candidates = COMMANDS.iter().filter(|command| command[1..].starts_with(word));
+        return (0, candidates.map(|command| command.to_string()).collect());
+    }
+    if word.is_empty() {
+        return (pos, Vec::new());
+    }
+    let mut candidates: Vec<String> = KEYWORDS
+        .iter()
+        .map(|keyword| keyword.to_string())
+        .chain(Builtin::ALL.iter().map(|builtin| builtin.name().to_string()))
+        .chain(names.iter().cloned())
+        .filter(|candidate| candidate.starts_with(word))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+#[cfg(test)]
+mod interactive_tester {
+    use super::complete_word;
+
+    #[test]
+    fn test_complete_word() {
+        let names = vec![String::from("length"), String::from("letters"), String::from("größe")];
+        let tests = vec![
+            ("le", 2, (0, vec!["length", "let", "letters"])),
+            ("1 + gr", 6, (4, vec!["größe"])),
+            ("f(tr", 4, (2, vec!["true"])),
+            ("pu", 2, (0, vec!["puts"])),
+            ("lex", 3, (0, vec![])),
+            ("x + ", 4, (4, vec![])),
+            ("let x = re; y", 10, (8, vec!["return"])),
+            (":t", 2, (0, vec![":time", ":tokens"])),
+            (":", 1, (0, vec![":ast", ":env", ":help", ":load", ":reset", ":time", ":tokens"])),
+        ];
+
+        for (line, pos, (start, candidates)) in tests {
+            let candidates: Vec<String> = candidates.iter().map(|c| c.to_string()).collect();
+            assert_eq!(complete_word(line, pos, &names), (start, candidates), "{}", line);
+        }
+    }
+}
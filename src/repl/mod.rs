@@ -1 +1,3 @@
 pub mod repl;
+pub mod interactive;
+pub mod protocol;
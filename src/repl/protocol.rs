@@ -0,0 +1,307 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{BufRead, ErrorKind, Write},
+    mem,
+    rc::Rc,
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    diagnostics::diagnostics::Diagnostic,
+    evaluator::evaluator::eval,
+    lexer::lexer::Lexer,
+    object::environment::{Env, Environment},
+    parser::parser::Parser,
+    repl::repl::read_lines,
+};
+
+/// The code reported for lines that are not a valid request.
+const INVALID_REQUEST: &str = "E0300";
+/// The code reported when reading the next request fails.
+const READ_FAILED: &str = "E0301";
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    code: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Response {
+    id: Value,
+    /// The displayed result, or null if there were errors.
+    value: Option<String>,
+    #[serde(rename = "type")]
+    type_name: Option<&'static str>,
+    stdout: String,
+    errors: Vec<Error>,
+    time_us: u128,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct Error {
+    code: &'static str,
+    message: String,
+    span: Option<SpanJson>,
+    label: Option<String>,
+    notes: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct SpanJson {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+    /// The id of the request whose `code` the span points into.
+    request: Value,
+}
+
+impl Error {
+    fn new(diagnostic: Diagnostic, span: Option<SpanJson>) -> Self {
+        Self {
+            code: diagnostic.code,
+            message: diagnostic.message,
+            span,
+            label: diagnostic.label,
+            notes: diagnostic.notes,
+        }
+    }
+}
+
+/// Where the code of every request starts among all the code of the
+/// session. Each request is lexed with its start as the offset, so a span
+/// tells which request it points into.
+#[derive(Default)]
+struct Requests {
+    starts: Vec<(usize, Value)>,
+    end: usize,
+}
+
+impl Requests {
+    /// Records the next request and returns the offset its code starts at.
+    fn push(&mut self, id: Value, code: &str) -> usize {
+        let start = self.end;
+        // The gap keeps a span at the very end of one request's code, like
+        // that of an unexpected EOF, from also belonging to the next.
+        self.end += code.len() + 1;
+        self.starts.push((start, id));
+        start
+    }
+
+    fn error(&self, diagnostic: Diagnostic) -> Error {
+        let span = diagnostic.span.and_then(|span| {
+            let (start, id) = self.starts.iter().rev().find(|(start, _)| *start <= span.start)?;
+            Some(SpanJson {
+                start: span.start - start,
+                end: span.end - start,
+                line: span.line,
+                column: span.column,
+                request: id.clone(),
+            })
+        });
+        Error::new(diagnostic, span)
+    }
+}
+
+/// Serves the REPL over JSON lines for editors and notebooks. Each input
+/// line is a request like `{"id": 1, "code": "let x = 5; x * 2"}` and gets
+/// exactly one response line with the same `id`:
+///
+/// ```text
+/// {"id":1,"value":"10","type":"INTEGER","stdout":"","errors":[],"time_us":12}
+/// ```
+///
+/// Requests share one environment, like lines typed at the prompt. Output
+/// from `puts` is returned in `stdout` rather than written to the stream.
+/// Parse and runtime errors are returned in `errors`. Their spans are byte
+/// offsets into the `code` of the request named by the span's `request`,
+/// which is an earlier one when the error was raised inside a function that
+/// request defined. A line that cannot be read at all is answered with an
+/// error and ends the session.
+pub fn protocol(stdin: impl BufRead, mut stdout: impl Write) {
+    let output = Rc::new(RefCell::new(String::new()));
+    let env = Environment::with_output(Rc::clone(&output));
+    let mut requests = Requests::default();
+    for line in read_lines(stdin) {
+        let start = Instant::now();
+        let mut response = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => match serde_json::from_str::<Request>(&line) {
+                Ok(request) => {
+                    let mut response = respond(request, &env, &mut requests);
+                    response.stdout = mem::take(&mut *output.borrow_mut());
+                    response
+                }
+                Err(error) => invalid_request(error),
+            },
+            Err(error) if error.kind() == ErrorKind::InvalidData => invalid_request(error),
+            Err(error) => error_response(Diagnostic::new(READ_FAILED, format!("could not read request: {}", error))),
+        };
+        response.time_us = start.elapsed().as_micros();
+        let response = serde_json::to_string(&response).expect("responses always serialize");
+        writeln!(stdout, "{}", response).expect("Failed to write to stdout");
+        stdout.flush().expect("Failed to flush stdout");
+    }
+}
+
+/// The response to a line that is not a request, which has no id to echo.
+fn invalid_request(error: impl fmt::Display) -> Response {
+    error_response(Diagnostic::new(INVALID_REQUEST, format!("invalid request: {}", error)))
+}
+
+fn error_response(diagnostic: Diagnostic) -> Response {
+    Response {
+        id: Value::Null,
+        value: None,
+        type_name: None,
+        stdout: String::new(),
+        errors: vec![Error::new(diagnostic, None)],
+        time_us: 0,
+    }
+}
+
+fn respond(request: Request, env: &Env, requests: &mut Requests) -> Response {
+    let mut response = Response {
+        id: request.id,
+        value: None,
+        type_name: None,
+        stdout: String::new(),
+        errors: Vec::new(),
+        time_us: 0,
+    };
+    let offset = requests.push(response.id.clone(), &request.code);
+    let mut parser = Parser::new(Lexer::new(&request.code).with_offset(offset));
+    let program = parser.parse_program();
+    if !parser.errors().is_empty() {
+        response.errors = parser.errors().iter().map(|error| requests.error(Diagnostic::from(error))).collect();
+        return response;
+    }
+    let result = eval(&program, env);
+    match Diagnostic::from_object(&result) {
+        Some(diagnostic) => response.errors.push(requests.error(diagnostic)),
+        None => {
+            response.value = Some(result.to_string());
+            response.type_name = Some(result.type_name());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod protocol_tester {
+    use std::io::{self, BufRead, BufReader, Read};
+
+    use serde_json::{json, Value};
+
+    use super::protocol;
+
+    fn run(input: impl AsRef<[u8]>) -> Vec<Value> {
+        run_reader(input.as_ref())
+    }
+
+    fn run_reader(input: impl BufRead) -> Vec<Value> {
+        let mut output = Vec::new();
+        protocol(input, &mut output);
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut response: Value = serde_json::from_str(line).unwrap();
+                assert!(response["time_us"].is_u64(), "{}", line);
+                response.as_object_mut().unwrap().remove("time_us");
+                response
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_values_and_session() {
+        let responses = run(r#"{"id": 1, "code": "let x = 5;"}
+{"id": "two", "code": "[x * 2, \"a\"]"}
+"#);
+        assert_eq!(
+            responses,
+            vec![
+                json!({"id": 1, "value": "null", "type": "NULL", "stdout": "", "errors": []}),
+                json!({"id": "two", "value": "[10, \"a\"]", "type": "ARRAY", "stdout": "", "errors": []}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_captured_stdout() {
+        let responses = run(r#"{"id": 1, "code": "puts(\"hi\", 1 + 1); 3"}
+{"id": 2, "code": "4"}
+"#);
+        assert_eq!(responses[0]["stdout"], "hi\n2\n");
+        assert_eq!(responses[0]["value"], "3");
+        assert_eq!(responses[1]["stdout"], "");
+    }
+
+    #[test]
+    fn test_errors() {
+        let responses = run(r#"{"id": 1, "code": "1 +\n true"}
+{"id": 2, "code": "let = 1;"}
+not json
+"#);
+        assert_eq!(
+            responses[0],
+            json!({"id": 1, "value": null, "type": null, "stdout": "", "errors": [{
+                "code": "E0100",
+                "message": "type mismatch: INTEGER + BOOLEAN",
+                "span": {"start": 0, "end": 9, "line": 1, "column": 1, "request": 1},
+                "label": null,
+                "notes": [],
+            }]})
+        );
+        assert_eq!(responses[1]["errors"][0]["code"], "E0010");
+        assert_eq!(responses[1]["errors"][0]["span"], json!({"start": 4, "end": 5, "line": 1, "column": 5, "request": 2}));
+        assert_eq!(responses[2]["id"], Value::Null);
+        assert_eq!(responses[2]["errors"][0]["code"], "E0300");
+        assert!(responses[2]["errors"][0]["message"].as_str().unwrap().starts_with("invalid request: "));
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let responses = run(b"{\"id\": 1, \"code\": \"\xff\"}\n{\"id\": 2, \"code\": \"2\"}\n");
+        assert_eq!(responses[0]["id"], Value::Null);
+        assert_eq!(responses[0]["errors"][0]["code"], "E0300");
+        assert!(responses[0]["errors"][0]["message"].as_str().unwrap().starts_with("invalid request: "));
+        assert_eq!(responses[1]["value"], "2");
+    }
+
+    #[test]
+    fn test_spans_name_their_request() {
+        let responses = run(r#"{"id": "def", "code": "let f = fn(x) {\n  x + true\n};"}
+{"id": "call", "code": "f(1)"}
+"#);
+        assert_eq!(
+            responses[1]["errors"][0]["span"],
+            json!({"start": 18, "end": 26, "line": 2, "column": 3, "request": "def"})
+        );
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    fn test_read_errors_end_the_session() {
+        let input = BufReader::new(&b"{\"id\": 1, \"code\": \"1\"}\n"[..]).chain(Failing);
+        let responses = run_reader(BufReader::new(input));
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["value"], "1");
+        assert_eq!(responses[1]["errors"][0]["code"], "E0301");
+        assert_eq!(responses[1]["errors"][0]["message"], "could not read request: disk on fire");
+    }
+}
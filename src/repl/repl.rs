@@ -1,17 +1,478 @@
-use std::io::{Write, BufRead};
+use std::{
+    fs,
+    io::{self, BufRead, ErrorKind, Write},
+    iter,
+    mem,
+    slice,
+    time::Instant,
+};
 
-use crate::lexer::lexer::Lexer;
+use crate::{
+    ast::ast::Program,
+    diagnostics::diagnostics::{render, Diagnostic, Style},
+    evaluator::evaluator::eval,
+    lexer::lexer::Lexer,
+    object::{
+        environment::{Env, Environment},
+        object::Object,
+    },
+    parser::{error::ParseError, parser::Parser},
+    token::token::Span,
+};
 
+pub const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+
+const HELP: &str = ":tokens <code>  print the tokens the lexer produces
+:ast <code>     print the parsed program, fully parenthesized
+:env            list the bindings in the session
+:load <file>    run a script in the session
+:reset          clear every binding
+:time <code>    evaluate and report how long it took
+:help           show this message
+";
+
+/// Reads Monkey source, evaluating each complete program in an environment
+/// shared by the whole session and printing its value. Parse and runtime
+/// errors are reported without ending the session.
+///
+/// Input that only fails to parse because it stops early, like an open
+/// `{`, a trailing operator or an unterminated string, is continued on the
+/// next line. An empty line submits it as it is.
+///
+/// Lines starting with `:` are commands for inspecting the session; see
+/// `HELP`.
 pub fn repl(stdin: impl BufRead, mut stdout: impl Write) {
-    write!(stdout, ">> ").expect("Failed to write to stdout");
-    stdout.flush().expect("Failed to flush stdout");
-    for line in stdin.lines() {
-        let lexer = Lexer::new(line.unwrap());
-        for token in lexer {
-            writeln!(stdout, "{:?}", token).expect("Failed to write to stdout");
+    let mut session = Session::new();
+    let mut prompt = PROMPT;
+    write_prompt(&mut stdout, prompt);
+    for line in read_lines(stdin) {
+        match line {
+            Ok(line) => prompt = session.feed(&line, &mut stdout),
+            // Only the bad line is dropped; pending input is kept.
+            Err(error) if error.kind() == ErrorKind::InvalidData => {
+                let diagnostic = Diagnostic::new("E0002", "input is not valid UTF-8").with_note("the line was skipped");
+                write!(stdout, "{}", render("<repl>", "", &[diagnostic], Style::Plain)).expect("Failed to write to stdout");
+            }
+            Err(error) => {
+                writeln!(stdout, "could not read input: {}", error).expect("Failed to write to stdout");
+                break;
+            }
+        }
+        write_prompt(&mut stdout, prompt);
+    }
+    session.finish(&mut stdout);
+}
+
+/// Like `BufRead::lines`, except that a line that is not valid UTF-8 is
+/// returned as an `InvalidData` error without ending the stream. Any other
+/// error ends it.
+pub(crate) fn read_lines(mut input: impl BufRead) -> impl Iterator<Item = io::Result<String>> {
+    let mut failed = false;
+    iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let mut line = Vec::new();
+        match input.read_until(b'\n', &mut line) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(error) => {
+                failed = true;
+                return Some(Err(error));
+            }
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
         }
-        write!(stdout, ">> ").expect("Failed to write to stdout");
-        stdout.flush().expect("Failed to flush stdout");
+        Some(String::from_utf8(line).map_err(|error| io::Error::new(ErrorKind::InvalidData, error)))
+    })
+}
+
+/// The state `repl` keeps between lines, shared with the interactive
+/// line editor.
+pub struct Session {
+    env: Env,
+    input: String,
+    sources: Sources,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            env: Environment::new(),
+            input: String::new(),
+            sources: Sources::default(),
+        }
+    }
+
+    /// Handles one line of input and returns the prompt for the next one.
+    pub fn feed(&mut self, line: &str, mut stdout: impl Write) -> &'static str {
+        if self.input.is_empty() && line.starts_with(':') {
+            self.run_command(line, &mut stdout);
+            return PROMPT;
+        }
+        if !self.input.is_empty() {
+            self.input.push('\n');
+        }
+        self.input.push_str(line);
+
+        let input = mem::take(&mut self.input);
+        let (program, errors) = self.parse(&input);
+        let incomplete = !errors.is_empty() && errors.iter().all(ParseError::is_incomplete_input);
+        if incomplete && !line.trim().is_empty() {
+            self.input = input;
+            return CONTINUATION_PROMPT;
+        }
+        self.execute("<repl>", input, (program, errors), false, &mut stdout);
+        PROMPT
+    }
+
+    /// Runs whatever input is still pending when the stream ends.
+    pub fn finish(&mut self, stdout: impl Write) {
+        if !self.input.is_empty() {
+            let input = mem::take(&mut self.input);
+            let parsed = self.parse(&input);
+            self.execute("<repl>", input, parsed, false, stdout);
+        }
+    }
+
+    /// Drops pending input, as when the user presses Ctrl-C mid-program.
+    pub fn cancel(&mut self) {
+        self.input.clear();
+    }
+
+    /// The names bound in the session, for completion.
+    pub fn names(&self) -> Vec<String> {
+        self.env.borrow().bindings().into_iter().map(|(name, _)| name).collect()
+    }
+
+    fn run_command(&mut self, line: &str, mut stdout: impl Write) {
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+        match command {
+            ":tokens" => {
+                for token in Lexer::new(argument).with_trivia() {
+                    let (line, column) = (token.span.line, token.span.column);
+                    writeln!(stdout, "{}:{} {:?} {:?}", line, column, token.token_type, token.literal)
+                        .expect("Failed to write to stdout");
+                }
+            }
+            ":ast" => {
+                let mut parser = Parser::new(Lexer::new(argument));
+                let program = parser.parse_program();
+                if report_parse_errors("<repl>", argument, parser.errors(), &mut stdout) {
+                    return;
+                }
+                for statement in &program {
+                    writeln!(stdout, "{}", statement).expect("Failed to write to stdout");
+                }
+            }
+            ":env" => {
+                for (name, value) in self.env.borrow().bindings() {
+                    writeln!(stdout, "{} = {}", name, value).expect("Failed to write to stdout");
+                }
+            }
+            ":load" => match fs::read_to_string(argument) {
+                Ok(source) => {
+                    let parsed = self.parse(&source);
+                    self.execute(argument, source, parsed, false, stdout);
+                }
+                Err(error) => writeln!(stdout, "could not read {}: {}", argument, error).expect("Failed to write to stdout"),
+            },
+            ":reset" => {
+                self.env = Environment::new();
+                self.sources = Sources::default();
+            }
+            ":time" => {
+                let parsed = self.parse(argument);
+                self.execute("<repl>", String::from(argument), parsed, true, stdout);
+            }
+            ":help" => write!(stdout, "{}", HELP).expect("Failed to write to stdout"),
+            _ => writeln!(stdout, "unknown command `{}`, try :help", command).expect("Failed to write to stdout"),
+        }
+    }
+
+    /// Parses `source` as the next program of the session.
+    fn parse(&self, source: &str) -> (Program, Vec<ParseError>) {
+        let mut parser = Parser::new(Lexer::new(source).with_offset(self.sources.next_start()));
+        let program = parser.parse_program();
+        (program, parser.errors().to_vec())
+    }
+
+    /// Evaluates a program `parse` returned for `source`, printing its value
+    /// or its errors, and with `timed`, how long evaluation alone took.
+    fn execute(
+        &mut self,
+        name: &str,
+        source: String,
+        (program, errors): (Program, Vec<ParseError>),
+        timed: bool,
+        mut stdout: impl Write,
+    ) {
+        self.sources.push(name, source);
+        if !errors.is_empty() {
+            let diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+            write!(stdout, "{}", self.sources.render(&diagnostics)).expect("Failed to write to stdout");
+            return;
+        }
+        let start = Instant::now();
+        let result = eval(&program, &self.env);
+        let elapsed = start.elapsed();
+        match result {
+            // Statements like `let` have no value worth echoing.
+            Object::Null => {}
+            result => match Diagnostic::from_object(&result) {
+                Some(diagnostic) => {
+                    write!(stdout, "{}", self.sources.render(&[diagnostic])).expect("Failed to write to stdout")
+                }
+                None => writeln!(stdout, "{}", result).expect("Failed to write to stdout"),
+            },
+        }
+        if timed {
+            writeln!(stdout, "time: {:.2?}", elapsed).expect("Failed to write to stdout");
+        }
+    }
+}
+
+/// Every program run in the session, laid end to end. Each is lexed with
+/// the offset it starts at, so an error raised in a function that an
+/// earlier input defined is shown against that input.
+#[derive(Default)]
+struct Sources {
+    sources: Vec<Source>,
+    end: usize,
+}
+
+struct Source {
+    name: String,
+    text: String,
+    start: usize,
+}
+
+impl Sources {
+    fn next_start(&self) -> usize {
+        self.end
+    }
+
+    fn push(&mut self, name: &str, text: String) {
+        let start = self.end;
+        // The gap keeps a span at the very end of one source, like that of
+        // an unexpected EOF, from also belonging to the next.
+        self.end += text.len() + 1;
+        self.sources.push(Source { name: String::from(name), text, start });
+    }
+
+    fn render(&self, diagnostics: &[Diagnostic]) -> String {
+        let mut output = String::new();
+        for diagnostic in diagnostics {
+            let source = diagnostic
+                .span
+                .and_then(|span| self.sources.iter().rev().find(|source| source.start <= span.start));
+            let Some(source) = source else {
+                output += &render("<repl>", "", slice::from_ref(diagnostic), Style::Plain);
+                continue;
+            };
+            let mut diagnostic = diagnostic.clone();
+            diagnostic.span = diagnostic.span.map(|span| {
+                Span::new(span.start - source.start, span.end - source.start, span.line, span.column)
+            });
+            output += &render(&source.name, &source.text, &[diagnostic], Style::Plain);
+        }
+        output
+    }
+}
+
+/// Renders `errors`, if any, and says whether there were some.
+fn report_parse_errors(path: &str, source: &str, errors: &[ParseError], mut stdout: impl Write) -> bool {
+    if errors.is_empty() {
+        return false;
+    }
+    let diagnostics: Vec<Diagnostic> = errors.iter().map(Diagnostic::from).collect();
+    write!(stdout, "{}", render(path, source, &diagnostics, Style::Plain)).expect("Failed to write to stdout");
+    true
+}
+
+fn write_prompt(mut stdout: impl Write, prompt: &str) {
+    write!(stdout, "{}", prompt).expect("Failed to write to stdout");
+    stdout.flush().expect("Failed to flush stdout");
+}
+
+#[cfg(test)]
+mod repl_tester {
+    use super::repl;
+
+    fn run(input: &str) -> String {
+        let mut output = Vec::new();
+        repl(input.as_bytes(), &mut output);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_session_environment_persists() {
+        let output = run("let x = 5;\nx * 2\nlet greet = fn(name) { \"hi \" + name };\ngreet(\"you\")\n");
+        assert_eq!(output, ">> >> 10\n>> >> hi you\n>> ");
+    }
+
+    #[test]
+    fn test_errors_point_into_earlier_inputs() {
+        let output = run("let f = fn(x) { x + true };\n\nf(1)\n");
+        assert_eq!(
+            output,
+            ">> >> >> error[E0100]: type mismatch: INTEGER + BOOLEAN
+ --> <repl>:1:17
+  |
+1 | let f = fn(x) { x + true };
+  |                 ^^^^^^^^
+>> "
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_skipped() {
+        let mut output = Vec::new();
+        repl(&b"let x = [1,\n\xff\n2]\r\nx\n"[..], &mut output);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">> .. error[E0002]: input is not valid UTF-8
+  = help: the line was skipped
+.. >> [1, 2]\n>> "
+        );
+    }
+
+    #[test]
+    fn test_values_use_display() {
+        let output = run("[1, \"two\", 3.0]\n{\"a\": true}\n");
+        assert_eq!(output, ">> [1, \"two\", 3.0]\n>> {\"a\": true}\n>> ");
+    }
+
+    #[test]
+    fn test_errors_do_not_end_session() {
+        let output = run("let = 1;\n1 + true\n1 + 1\n");
+        assert_eq!(
+            output,
+            ">> error[E0010]: expected next token to be IDENT, got ASSIGN instead
+ --> <repl>:1:5
+  |
+1 | let = 1;
+  |     ^ expected IDENT
+>> error[E0100]: type mismatch: INTEGER + BOOLEAN
+ --> <repl>:1:1
+  |
+1 | 1 + true
+  | ^^^^^^^^
+>> 2
+>> "
+        );
+    }
+
+    #[test]
+    fn test_multi_line_input() {
+        let output = run("let add = fn(a, b) {\n  a + b\n};\nadd(1,\n  2)\n");
+        assert_eq!(output, ">> .. .. >> .. 3\n>> ");
+    }
+
+    #[test]
+    fn test_continues_after_trailing_operator_and_open_string() {
+        let output = run("1 +\n2\n\"a\n b\"\n");
+        assert_eq!(output, ">> .. 3\n>> .. a\n b\n>> ");
+    }
+
+    #[test]
+    fn test_empty_line_submits_incomplete_input() {
+        let output = run("fn(x) {\n\n1\n");
+        assert_eq!(
+            output,
+            ">> .. error[E0012]: expected RBRACE to close block, got EOF instead
+ --> <repl>:2:1
+  |
+2 | 
+  | ^ input ends here
+  = help: the block was opened at 1:7
+>> 1
+>> "
+        );
+    }
+
+    #[test]
+    fn test_real_errors_are_reported_immediately() {
+        let output = run("x) + (\n");
+        assert!(output.starts_with(">> error[E0011]"), "{}", output);
+    }
+
+    #[test]
+    fn test_pending_input_is_reported_at_end_of_stream() {
+        let output = run("[1,\n2");
+        assert_eq!(
+            output,
+            ">> .. .. error[E0010]: expected next token to be RBRACKET, got EOF instead
+ --> <repl>:2:2
+  |
+2 | 2
+  |  ^ expected RBRACKET
+"
+        );
+    }
+
+    #[test]
+    fn test_tokens_command() {
+        let output = run(":tokens let x = 1; // one\n");
+        assert_eq!(
+            output,
+            r#">> 1:1 LET "let"
+1:5 IDENT "x"
+1:7 ASSIGN "="
+1:9 INT "1"
+1:10 SEMICOLON ";"
+1:12 COMMENT "// one"
+1:18 EOF ""
+>> "#
+        );
+    }
+
+    #[test]
+    fn test_ast_command() {
+        let output = run(":ast let x = 1 + 2 * 3; -a[0]\n:ast let = 1;\n");
+        assert!(output.starts_with(">> let x = (1 + (2 * 3));\n(-(a[0]))\n>> error[E0010]"), "{}", output);
+    }
+
+    #[test]
+    fn test_env_and_reset_commands() {
+        let output = run("let b = [1];\nlet a = \"x\";\n:env\n:reset\n:env\na\n");
+        assert!(output.starts_with(">> >> >> a = x\nb = [1]\n>> >> >> error[E0100]: identifier not found: a"), "{}", output);
+    }
+
+    #[test]
+    fn test_load_command() {
+        let path = std::env::temp_dir().join(format!("repl_load_{}.monkey", std::process::id()));
+        std::fs::write(&path, "let double = fn(x) {\n  x * 2\n};\ndouble(4)\n").unwrap();
+        let output = run(&format!(":load {}\ndouble(5)\n:load /no/such/file\n", path.display()));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(output.lines().take(2).collect::<Vec<_>>(), vec![">> 8", ">> 10"]);
+        assert!(output.contains(">> could not read /no/such/file: "), "{}", output);
+    }
+
+    #[test]
+    fn test_time_command() {
+        let output = run("let x = 20;\n:time x + 1\n");
+        assert!(output.starts_with(">> >> 21\ntime: "), "{}", output);
+        let output = run(":time 1 +\n");
+        assert!(!output.contains("time: "), "{}", output);
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        assert_eq!(run(":nope\n"), ">> unknown command `:nope`, try :help\n>> ");
+    }
+}
@@ -1,15 +1,61 @@
+use std::borrow::Cow;
+
+/// A lexed token. The lexer hands out literals borrowed from the source;
+/// anything that outlives it, such as the AST, holds `Token<'static>`.
 #[derive(Debug, PartialEq, Clone, Eq)]
-pub struct Token {
+pub struct Token<'src> {
     pub token_type: Tokens,
-    pub literal: String
+    pub literal: Cow<'src, str>,
+    pub span: Span,
+}
+
+impl Token<'_> {
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            token_type: self.token_type,
+            literal: Cow::Owned(self.literal.into_owned()),
+            span: self.span,
+        }
+    }
+}
+
+/// Where a token sits in the source: `start..end` are byte offsets, `line`
+/// and `column` are 1-based and point at the first character.
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Default, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self { start, end, line, column }
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        let (first, last) = if self.start <= other.start { (self, other) } else { (other, self) };
+        Span {
+            start: first.start,
+            end: first.end.max(last.end),
+            line: first.line,
+            column: first.column,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
 pub enum Tokens {
     ILLEGAL,
     EOF,
+    COMMENT,
+    UNTERMINATEDCOMMENT,
     IDENT,
     INT,
+    FLOAT,
+    STRING,
     ASSIGN,
     PLUS,
     COMMA,
@@ -18,6 +64,9 @@ pub enum Tokens {
     RPAREN,
     LBRACE,
     RBRACE,
+    LBRACKET,
+    RBRACKET,
+    COLON,
     FUNCTION,
     LET,
     BANG,
@@ -32,5 +81,10 @@ pub enum Tokens {
     ELSE,
     RETURN,
     EQ,
-    NOTEQ
+    NOTEQ,
+    LTEQ,
+    GTEQ,
+    PERCENT,
+    AND,
+    OR,
 }
@@ -0,0 +1,743 @@
+use std::{borrow::Cow, cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use crate::{
+    code::code::{read_u16, Opcode},
+    compiler::compiler::Bytecode,
+    evaluator::{
+        builtins::call_builtin,
+        evaluator::{eval_index_expression, eval_infix_expression, eval_prefix_expression, MAX_CALL_DEPTH},
+    },
+    object::{
+        collector::Collector,
+        object::{Builtin, Cell, Closure, CompiledFunction, HashKey, Object},
+    },
+    token::token::{Span, Token, Tokens},
+};
+
+/// The stack grows as needed; this is only its initial capacity.
+const INITIAL_STACK_SIZE: usize = 2048;
+pub const MAX_FRAMES: usize = MAX_CALL_DEPTH;
+
+/// A function activation: the closure being run, the offset of the next
+/// instruction, where its arguments and locals start on the stack and the
+/// cells of the locals its nested functions capture.
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base_pointer: usize,
+    cells: Vec<Cell>,
+}
+
+/// Runs bytecode produced by the compiler. Operators and indexing share
+/// their implementation with the evaluator, so both backends agree on
+/// results and error messages.
+pub struct Vm {
+    constants: Vec<Object>,
+    global_names: Vec<String>,
+    globals: Vec<Option<Object>>,
+    stack: Vec<Object>,
+    frames: Vec<Frame>,
+    /// Cells gathered by `OpCaptureCell` and `OpCaptureFree` for the next
+    /// `OpClosure`.
+    captured: Vec<Cell>,
+    /// Watches cells that outlived their frame, which the closures stored
+    /// in them may keep alive in a cycle.
+    collector: Collector,
+    /// Where `puts` writes, like `Environment::with_output`; stdout if
+    /// `None`.
+    output: Option<Rc<RefCell<String>>>,
+    last_popped: Object,
+}
+
+impl Vm {
+    pub fn new(bytecode: Bytecode) -> Self {
+        let main = CompiledFunction {
+            instructions: bytecode.instructions,
+            source_map: bytecode.source_map,
+            num_locals: 0,
+            num_parameters: 0,
+            cell_names: Vec::new(),
+            free_names: Vec::new(),
+            literal: String::from("<main>"),
+        };
+        let main = Frame {
+            closure: Rc::new(Closure {
+                function: Rc::new(main),
+                free: Vec::new(),
+            }),
+            ip: 0,
+            base_pointer: 0,
+            cells: Vec::new(),
+        };
+        Self {
+            constants: bytecode.constants,
+            globals: vec![None; bytecode.global_names.len()],
+            global_names: bytecode.global_names,
+            stack: Vec::with_capacity(INITIAL_STACK_SIZE),
+            frames: vec![main],
+            captured: Vec::new(),
+            collector: Collector::default(),
+            output: None,
+            last_popped: Object::Null,
+        }
+    }
+
+    /// Collects the output of `puts` in `output` instead of printing it.
+    pub fn with_output(mut self, output: Rc<RefCell<String>>) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Runs the program to completion and returns the value of its last
+    /// statement, or the first runtime error.
+    pub fn run(&mut self) -> Object {
+        let result = match self.execute() {
+            Ok(value) => value,
+            Err(error) => error,
+        };
+        self.collector.collect();
+        result
+    }
+
+    fn execute(&mut self) -> Result<Object, Object> {
+        loop {
+            let frame = self.frames.last_mut().expect("the main frame is never popped");
+            let instructions = &frame.closure.function.instructions;
+            let offset = frame.ip;
+            let Some(&byte) = instructions.get(offset) else {
+                return Ok(self.last_popped.clone());
+            };
+            let op = Opcode::try_from(byte).unwrap_or_else(|byte| panic!("unknown opcode {}", byte));
+            frame.ip += op.width();
+
+            match op {
+                Opcode::Constant => {
+                    let index = self.read_u16(offset);
+                    self.push(self.constants[index].clone());
+                }
+                Opcode::Pop => self.last_popped = self.pop(),
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::LessThan
+                | Opcode::GreaterThan
+                | Opcode::LessEqual
+                | Opcode::GreaterEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = eval_infix_expression(&operator(op), left, right, Span::default());
+                    self.push_result(result, offset)?;
+                }
+                Opcode::Minus | Opcode::Bang => {
+                    let right = self.pop();
+                    let result = eval_prefix_expression(&operator(op), right, Span::default());
+                    self.push_result(result, offset)?;
+                }
+                Opcode::Truthy => {
+                    let value = self.pop();
+                    self.push(Object::Boolean(value.is_truthy()));
+                }
+                Opcode::True => self.push(Object::Boolean(true)),
+                Opcode::False => self.push(Object::Boolean(false)),
+                Opcode::Null => self.push(Object::Null),
+                Opcode::Jump => {
+                    let target = self.read_u16(offset);
+                    self.current_frame().ip = target;
+                }
+                Opcode::JumpNotTruthy => {
+                    let target = self.read_u16(offset);
+                    if !self.pop().is_truthy() {
+                        self.current_frame().ip = target;
+                    }
+                }
+                Opcode::GetGlobal => {
+                    let index = self.read_u16(offset);
+                    let value = match self.globals[index].clone() {
+                        Some(value) => value,
+                        None => self.builtin(&self.global_names[index], offset)?,
+                    };
+                    self.push(value);
+                }
+                Opcode::SetGlobal => {
+                    let index = self.read_u16(offset);
+                    self.globals[index] = Some(self.pop());
+                }
+                Opcode::GetLocal => {
+                    let index = self.read_u8(offset);
+                    let slot = self.current_frame().base_pointer + index;
+                    let value = self.stack[slot].clone();
+                    self.push(value);
+                }
+                Opcode::SetLocal => {
+                    let index = self.read_u8(offset);
+                    let slot = self.current_frame().base_pointer + index;
+                    self.stack[slot] = self.pop();
+                }
+                Opcode::GetFree => {
+                    let index = self.read_u8(offset);
+                    let frame = self.frames.last().expect("the main frame is never popped");
+                    let value = match frame.closure.free[index].borrow().clone() {
+                        Some(value) => value,
+                        None => self.unbound(&frame.closure.function.free_names[index], offset)?,
+                    };
+                    self.push(value);
+                }
+                Opcode::GetCell => {
+                    let index = self.read_u8(offset);
+                    let frame = self.frames.last().expect("the main frame is never popped");
+                    let value = match frame.cells[index].borrow().clone() {
+                        Some(value) => value,
+                        None => self.unbound(&frame.closure.function.cell_names[index], offset)?,
+                    };
+                    self.push(value);
+                }
+                Opcode::SetCell => {
+                    let index = self.read_u8(offset);
+                    let value = self.pop();
+                    self.current_frame().cells[index].replace(Some(value));
+                }
+                Opcode::Array => {
+                    let count = self.read_u16(offset);
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.push(Object::Array(Rc::new(elements)));
+                }
+                Opcode::Hash => {
+                    let count = self.read_u16(offset);
+                    let items = self.stack.split_off(self.stack.len() - count);
+                    let mut hash = BTreeMap::new();
+                    let mut items = items.into_iter();
+                    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                        let key = HashKey::from_object(&key).expect("keys are checked by OpHashable");
+                        hash.insert(key, value);
+                    }
+                    self.push(Object::Hash(Rc::new(hash)));
+                }
+                Opcode::Hashable => {
+                    let key = self.stack.last().expect("stack underflow");
+                    if HashKey::from_object(key).is_none() {
+                        let message = format!("unusable as hash key: {}", key.type_name());
+                        return Err(self.error(message, offset));
+                    }
+                }
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    let result = eval_index_expression(left, index, Span::default());
+                    self.push_result(result, offset)?;
+                }
+                Opcode::Call => {
+                    let count = self.read_u8(offset);
+                    self.call(count, offset)?;
+                }
+                Opcode::ReturnValue | Opcode::Return => {
+                    let value = match op {
+                        Opcode::ReturnValue => self.pop(),
+                        _ => Object::Null,
+                    };
+                    if self.frames.len() == 1 {
+                        // `return` at the top level ends the program; the main
+                        // frame stays, finished, so `run` can be called again.
+                        let main = self.current_frame();
+                        main.ip = main.closure.function.instructions.len();
+                        self.last_popped = value.clone();
+                        return Ok(value);
+                    }
+                    let frame = self.frames.pop().expect("the main frame is never popped");
+                    self.stack.truncate(frame.base_pointer - 1);
+                    self.push(value);
+                    self.release(frame);
+                }
+                Opcode::Closure => {
+                    let index = self.read_u16(offset);
+                    let count = self.current_frame().closure.function.instructions[offset + 3] as usize;
+                    let function = match &self.constants[index] {
+                        Object::CompiledFunction(function) => Rc::clone(function),
+                        constant => panic!("not a compiled function: {}", constant.type_name()),
+                    };
+                    let free = self.captured.split_off(self.captured.len() - count);
+                    self.push(Object::Closure(Rc::new(Closure { function, free })));
+                }
+                Opcode::CaptureCell => {
+                    let index = self.read_u8(offset);
+                    let cell = Rc::clone(&self.current_frame().cells[index]);
+                    self.captured.push(cell);
+                }
+                Opcode::CaptureFree => {
+                    let index = self.read_u8(offset);
+                    let cell = Rc::clone(&self.current_frame().closure.free[index]);
+                    self.captured.push(cell);
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, count: usize, offset: usize) -> Result<(), Object> {
+        let callee = &self.stack[self.stack.len() - 1 - count];
+        let closure = match callee {
+            Object::Closure(closure) => Rc::clone(closure),
+            Object::Builtin(builtin) => {
+                let builtin = *builtin;
+                let arguments = self.stack.split_off(self.stack.len() - count);
+                self.pop();
+                let output = self.output.clone();
+                let result = call_builtin(builtin, arguments, &mut |text| match &output {
+                    Some(output) => output.borrow_mut().push_str(text),
+                    None => print!("{}", text),
+                });
+                return self.push_result(result, offset + 1);
+            }
+            callee => {
+                let message = format!("not a function: {}", callee.type_name());
+                return Err(self.error(message, offset));
+            }
+        };
+        let function = &closure.function;
+        if function.num_parameters != count {
+            let message = format!(
+                "wrong number of arguments: want={}, got={}",
+                function.num_parameters, count
+            );
+            return Err(self.error(message, offset + 1));
+        }
+        let base_pointer = self.stack.len() - count;
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(self.error(String::from("stack overflow"), offset + 1));
+        }
+        self.stack.resize(base_pointer + function.num_locals, Object::Null);
+        let cells = function.cell_names.iter().map(|_| Rc::new(RefCell::new(None))).collect();
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base_pointer,
+            cells,
+        });
+        Ok(())
+    }
+
+    /// Drops a returned frame, handing the cells that are still captured to
+    /// the collector.
+    fn release(&mut self, frame: Frame) {
+        let cells: Vec<_> = frame.cells.iter().filter(|cell| Rc::strong_count(cell) > 1).map(Rc::downgrade).collect();
+        drop(frame);
+        for cell in cells {
+            if cell.strong_count() > 0 {
+                self.collector.retain_cell(cell);
+            }
+        }
+    }
+
+    /// Names bound nowhere fall back to builtins, as in the evaluator.
+    fn builtin(&self, name: &str, offset: usize) -> Result<Object, Object> {
+        match Builtin::lookup(name) {
+            Some(builtin) => Ok(Object::Builtin(builtin)),
+            None => Err(self.error(format!("identifier not found: {}", name), offset)),
+        }
+    }
+
+    /// Reads a captured local before its `let` has run. The evaluator would
+    /// look the name up further out, which for a function's cells means the
+    /// globals.
+    fn unbound(&self, name: &str, offset: usize) -> Result<Object, Object> {
+        let index = self.global_names.iter().position(|global| global == name);
+        match index.and_then(|index| self.globals[index].clone()) {
+            Some(value) => Ok(value),
+            None => self.builtin(name, offset),
+        }
+    }
+
+    fn current_frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("the main frame is never popped")
+    }
+
+    /// Reads the two-byte operand of the instruction at `offset`.
+    fn read_u16(&self, offset: usize) -> usize {
+        let frame = self.frames.last().expect("the main frame is never popped");
+        read_u16(&frame.closure.function.instructions[offset + 1..]) as usize
+    }
+
+    /// Reads the one-byte operand of the instruction at `offset`.
+    fn read_u8(&self, offset: usize) -> usize {
+        let frame = self.frames.last().expect("the main frame is never popped");
+        frame.closure.function.instructions[offset + 1] as usize
+    }
+
+    fn push(&mut self, object: Object) {
+        self.stack.push(object);
+    }
+
+    /// Pushes the result of a shared operator, giving errors the span of the
+    /// instruction that raised them.
+    fn push_result(&mut self, result: Object, offset: usize) -> Result<(), Object> {
+        match result {
+            Object::Error(message, _) => Err(self.error(message, offset)),
+            result => {
+                self.push(result);
+                Ok(())
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Object {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn error(&self, message: String, offset: usize) -> Object {
+        let frame = self.frames.last().expect("the main frame is never popped");
+        Object::Error(message, frame.closure.function.source_map.get(offset))
+    }
+}
+
+// Closures bound in globals may still be in cycles with the cells they
+// captured, so those are collected once nothing else can reach them.
+impl Drop for Vm {
+    fn drop(&mut self) {
+        self.globals.clear();
+        self.stack.clear();
+        self.frames.clear();
+        self.captured.clear();
+        self.last_popped = Object::Null;
+        self.collector.collect();
+    }
+}
+
+/// The token an operator opcode was compiled from, for the operator
+/// functions shared with the evaluator.
+fn operator(op: Opcode) -> Token<'static> {
+    let (token_type, literal) = match op {
+        Opcode::Add => (Tokens::PLUS, "+"),
+        Opcode::Sub => (Tokens::MINUS, "-"),
+        Opcode::Mul => (Tokens::ASTERISK, "*"),
+        Opcode::Div => (Tokens::SLASH, "/"),
+        Opcode::Mod => (Tokens::PERCENT, "%"),
+        Opcode::Equal => (Tokens::EQ, "=="),
+        Opcode::NotEqual => (Tokens::NOTEQ, "!="),
+        Opcode::LessThan => (Tokens::LT, "<"),
+        Opcode::GreaterThan => (Tokens::GT, ">"),
+        Opcode::LessEqual => (Tokens::LTEQ, "<="),
+        Opcode::GreaterEqual => (Tokens::GTEQ, ">="),
+        Opcode::Minus => (Tokens::MINUS, "-"),
+        Opcode::Bang => (Tokens::BANG, "!"),
+        op => unreachable!("{:?} is not an operator", op),
+    };
+    Token {
+        token_type,
+        literal: Cow::Borrowed(literal),
+        span: Span::default(),
+    }
+}
+
+#[cfg(test)]
+mod vm_tester {
+    use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+    use crate::compiler::compiler::Compiler;
+    use crate::evaluator::evaluator::eval;
+    use crate::lexer::lexer::Lexer;
+    use crate::object::environment::Environment;
+    use crate::object::object::{HashKey, Object};
+    use crate::parser::parser::Parser;
+    use crate::token::token::Span;
+
+    use super::Vm;
+
+    fn test_vm(input: &str) -> Vm {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).unwrap();
+        Vm::new(compiler.bytecode())
+    }
+
+    /// The result of running `input` and what it printed.
+    fn test_run_output(input: &str) -> (Object, String) {
+        let output = Rc::new(RefCell::new(String::new()));
+        let result = test_vm(input).with_output(Rc::clone(&output)).run();
+        let output = output.borrow().clone();
+        (result, output)
+    }
+
+    fn test_run(input: &str) -> Object {
+        test_run_output(input).0
+    }
+
+    fn test_eval_output(input: &str) -> (Object, String) {
+        let output = Rc::new(RefCell::new(String::new()));
+        let mut parser = Parser::new(Lexer::new(input));
+        let result = eval(&parser.parse_program(), &Environment::with_output(Rc::clone(&output)));
+        let output = output.borrow().clone();
+        (result, output)
+    }
+
+    /// Every program the evaluator's tests run, and then some. The VM must
+    /// print the same result for each, and fail with the same message at the
+    /// same place.
+    const PROGRAMS: &[&str] = &[
+        "5;",
+        "-10;",
+        "5 + 5 + 5 + 5 - 10;",
+        "-50 + 100 + -50;",
+        "20 + 2 * -10;",
+        "(5 + 10 * 2 + 15 / 3) * 2 + -10;",
+        "1 < 2;",
+        "1 > 1;",
+        "1 != 2;",
+        "true != false;",
+        "(1 > 2) == false;",
+        "!5;",
+        "!!false;",
+        "if (true) { 10 }",
+        "if (false) { 10 }",
+        "if (1) { 10 }",
+        "if (1 > 2) { 10 } else { 20 }",
+        "if (true) { }",
+        "if (true) { let a = 1; }",
+        "if (true) { let a = 1; } a;",
+        "return 10; 9;",
+        "9; return 2 * 5; 9;",
+        "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
+        "let f = fn(x) { if (x > 1) { if (x > 2) { return 3; } return 2; } 1 }; f(5) + f(2) + f(0);",
+        "5 + true; 5;",
+        "-true;",
+        "5; true + false; 5;",
+        "return true + false; 5;",
+        "if (10 > 1) { if (10 > 1) { return true + false; } return 1; }",
+        "foobar;",
+        "10 / 0;",
+        r#""Hello" - "World";"#,
+        r#""a" + 1;"#,
+        "[1, 2][true];",
+        "5[0];",
+        r#"{"name": "Monkey"}[fn(x) { x }];"#,
+        "{[1]: 2};",
+        "1.5 + true;",
+        "{1.5: 1};",
+        "[1][0.0];",
+        "-true + 1.0;",
+        "5 % 0;",
+        "let min = -9223372036854775807 - 1; min % -1;",
+        "let min = -9223372036854775807 - 1; -min;",
+        "-9223372036854775808 == -9223372036854775807 - 1;",
+        "-9223372036854775808 - 1;",
+        "true && missing;",
+        "false || 1 / 0;",
+        "\"a\" <= \"b\";",
+        "true >= false;",
+        "9223372036854775807 + 1;",
+        r#""Hello" + " " + "World!";"#,
+        r#"let greet = fn(name) { "hi, " + name }; greet("\u{1F980}");"#,
+        r#""a" != "a";"#,
+        "[1, 2 * 2, 3 + 3]",
+        "let i = 0; [1][i];",
+        "let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]",
+        "[1, 2, 3][3]",
+        "[1, 2, 3][-1]",
+        "let nested = fn() { let xs = [[1], [2, 3]]; fn() { xs[1][0] } }; nested()()",
+        r#"let two = "two"; {"one": 10 - 9, two: 1 + 1, "thr" + "ee": 6 / 2, 4: 4, true: 5, false: 6}"#,
+        r#"{"foo": 5}["bar"]"#,
+        r#"let key = "foo"; {"foo": 5}[key]"#,
+        r#"{"a": 1, "a": 2}["a"]"#,
+        r#"[1, "two", [true], {"k": "v"}]"#,
+        "{}",
+        "0.1 * 3 - 0.3 < 1e-9;",
+        "7 / 2.0;",
+        "2 * 1.5 + 1;",
+        "1 == 1.0;",
+        "1.0 / 0;",
+        "0.0 / 0;",
+        "let nan = 0.0 / 0; nan == nan;",
+        "if (0.0) { 1 } else { 2 };",
+        "[1, 1.0];",
+        "2.5 >= 2;",
+        "-7 % 3;",
+        "7.5 % 2;",
+        "1.0 % 0;",
+        "1 && \"yes\";",
+        "if (false) { 1 } || 0;",
+        "false && missing;",
+        "true || 1 / 0;",
+        "let calls = fn(x) { x / 0 }; false && calls(1);",
+        "let calls = fn(x) { x / 0 }; true && calls(1);",
+        "let a = 5; let b = a; let c = a + b + 5; c;",
+        "let a = 5;",
+        "",
+        "let größe = 2; let x1 = 3; let 合计 = fn(a_1, b_2) { a_1 * b_2 }; 合计(größe, x1);",
+        "fn(x) { x + 2; };",
+        "let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));",
+        "fn(x) { x; }(5);",
+        "let f = fn() { return 1; 2; }; f() + 10;",
+        "let f = fn() { let a = 1; }; f();",
+        "let f = fn(x, x) { x }; f(1, 2);",
+        "let newAdder = fn(x) { fn(y) { x + y }; }; let addTwo = newAdder(2); addTwo(2);",
+        "let add = fn(a, b) { a + b }; let applyFunc = fn(a, b, func) { func(a, b) }; applyFunc(2, 2, add);",
+        "let x = 10; let f = fn(x) { fn() { x } }; f(1)() + x;",
+        "let curry = fn(a) { fn(b) { fn(c) { a * b + c } } }; curry(2)(3)(4);",
+        "let adder = fn(x) { fn(y) { x + y } }; let compose = fn(f, g) { fn(x) { g(f(x)) } }; compose(adder(2), adder(3))(1);",
+        "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);",
+        "let f = fn(n) { let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count(n) }; f(10);",
+        "let f = fn() { g() }; let g = fn() { 1 }; f();",
+        "let outer = fn() { let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } }; let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } }; even(4) }; outer();",
+        "let f = fn() { let g = fn() { x }; let x = 2; g() }; f();",
+        "let x = 1; let f = fn() { let g = fn() { x }; let a = g(); let x = 2; a + g() }; f();",
+        "let f = fn() { let g = fn() { y }; g() }; f();",
+        "let f = fn(x) { let g = fn() { x }; let x = x + 1; g() }; f(1);",
+        "let f = fn() { let puts = fn() { 1 }; let g = fn() { puts }; g() }; f();",
+        "let make = fn() { let count = fn(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count }; make()(3);",
+        "let wrap = fn(x) { fn() { x } }; let f = fn() { let h = fn() { h }; wrap(h) }; f()()()();",
+        "let f = fn() { g }; f();",
+        "let x = x;",
+        "let f = fn() { let y = y; }; f();",
+        "let f = fn(x) { x }; f(1, 2);",
+        "let x = 5; x(1);",
+        "let f = fn() { y }; f();",
+        "fn() { 1 } == fn() { 1 };",
+        "puts;",
+        "let puts = 1; puts;",
+        "puts(1, \"a\");",
+        "let f = fn(x) { puts(x); x * 2 }; puts(f(1), [f(2)]);",
+        "puts == puts;",
+        "let a = 1;\n-true;",
+        "let f = fn(x) { x };\nf(1, 2);",
+    ];
+
+    #[test]
+    fn matches_evaluator() {
+        for input in PROGRAMS {
+            let (expected, expected_output) = test_eval_output(input);
+            let (actual, actual_output) = test_run_output(input);
+            assert_eq!(actual.to_string(), expected.to_string(), "{}", input);
+            assert_eq!(actual_output, expected_output, "{}", input);
+            assert_eq!(actual.type_name(), expected.type_name(), "{}", input);
+            if let (Object::Error(_, actual), Object::Error(_, expected)) = (&actual, &expected) {
+                assert_eq!(actual, expected, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn run_after_top_level_return() {
+        let mut vm = test_vm("return 5; 6;");
+        assert_eq!(vm.run(), Object::Integer(5));
+        assert_eq!(vm.run(), Object::Integer(5));
+    }
+
+    #[test]
+    fn run_integer_arithmetic() {
+        let tests = vec![
+            ("1 + 2", 3),
+            ("50 / 2 * 2 + 10 - 5", 55),
+            ("5 * (2 + 10)", 60),
+            ("-50 + 100 + -50", 0),
+            ("7 % 3", 1),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_run(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn run_collections() {
+        assert_eq!(
+            test_run("[1, 2 * 2, 3 + 3]"),
+            Object::Array(Rc::new(vec![Object::Integer(1), Object::Integer(4), Object::Integer(6)]))
+        );
+        assert_eq!(
+            test_run(r#"{1: 2, "a": 3 * 4}"#),
+            Object::Hash(Rc::new(BTreeMap::from([
+                (HashKey::Integer(1), Object::Integer(2)),
+                (HashKey::String(String::from("a")), Object::Integer(12)),
+            ])))
+        );
+    }
+
+    #[test]
+    fn run_large_literals() {
+        let input = format!("[{}]", vec!["1"; 3000].join(", "));
+        match test_run(&input) {
+            Object::Array(elements) => assert_eq!(elements.len(), 3000),
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+
+    #[test]
+    fn run_closures() {
+        let tests = vec![
+            ("let newClosure = fn(a) { fn() { a; }; }; let closure = newClosure(99); closure();", 99),
+            ("let newAdder = fn(a, b) { fn(c) { a + b + c }; }; newAdder(1, 2)(8);", 11),
+            (
+                "let newAdderOuter = fn(a, b) { let c = a + b; fn(d) { let e = d + c; fn(f) { e + f; }; }; };
+                 let newAdderInner = newAdderOuter(1, 2); let adder = newAdderInner(3); adder(8);",
+                14,
+            ),
+            (
+                "let wrapper = fn() { let countDown = fn(x) { if (x == 0) { return 0; } else { countDown(x - 1); } }; countDown(1); }; wrapper();",
+                0,
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(test_run(input), Object::Integer(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn local_functions_are_released() {
+        let input = "let f = fn() { let xs = [1]; let even = fn(n) { if (n == 0) { xs } else { odd(n - 1) } };
+                     let odd = fn(n) { if (n == 0) { xs } else { even(n - 1) } }; even(2) }; f();";
+        match test_run(input) {
+            Object::Array(xs) => assert_eq!(Rc::strong_count(&xs), 1),
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+
+    #[test]
+    fn returned_recursive_functions_are_released() {
+        let make = "let make = fn(xs) { let count = fn(n) { if (n == 0) { xs } else { count(n - 1) } }; count };";
+        let tests = vec![
+            format!("{} let xs = [1]; make(xs); make(xs)(3)", make),
+            // Enough calls to collect while some of them are still running.
+            format!("{} let xs = [1]; let calls = fn(n) {{ if (n == 0) {{ make(xs)(2) }} else {{ calls(n - 1); calls(n - 1) }} }}; calls(9)", make),
+            format!("{} let xs = [1]; let count = make(xs); make(xs); count(3)", make),
+        ];
+        for input in tests {
+            match test_run(&input) {
+                Object::Array(xs) => assert_eq!(Rc::strong_count(&xs), 1, "{}", input),
+                object => panic!("Unexpected object {:?}", object),
+            }
+        }
+    }
+
+    #[test]
+    fn run_error_spans() {
+        let tests = vec![
+            ("5 + true;", Span::new(0, 8, 1, 1)),
+            ("foobar;", Span::new(0, 6, 1, 1)),
+            ("let x = 5;\nx(1);", Span::new(11, 12, 2, 1)),
+            ("let f = fn() { [1][true] };\nf();", Span::new(15, 24, 1, 16)),
+            ("let f = fn() { {[1]: 2} };\nf();", Span::new(16, 19, 1, 17)),
+        ];
+
+        for (input, expected) in tests {
+            match test_run(input) {
+                Object::Error(_, span) => assert_eq!(span, expected, "{}", input),
+                object => panic!("Unexpected object {:?} for {}", object, input),
+            }
+        }
+    }
+
+    #[test]
+    fn run_stack_overflow() {
+        match test_run("let f = fn(n) { f(n + 1) + 1 }; f(0);") {
+            Object::Error(message, span) => {
+                assert_eq!(message, "stack overflow");
+                assert_eq!(span, Span::new(16, 24, 1, 17));
+            }
+            object => panic!("Unexpected object {:?}", object),
+        }
+    }
+}